@@ -4,11 +4,16 @@
 
 extern crate nalgebra as na;
 use alga::general::ComplexField;
-use na::{DMatrix, Matrix4, Scalar};
+use approx::{AbsDiffEq, RelativeEq};
+use na::{DMatrix, Matrix4, Scalar, Vector4};
 
 const PRINT_NOTES: bool = false;
 
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
 use std::ops::Add;
+use std::ops::Deref;
 use std::ops::Div;
 use std::ops::Mul;
 use std::ops::Neg;
@@ -90,6 +95,11 @@ fn cross(a: Tuple, b: Tuple) -> Tuple {
     )
 }
 
+// reflects the incoming vector around the normal
+fn reflect(incoming: Tuple, normal: Tuple) -> Tuple {
+    incoming - normal * 2.0 * dot(incoming, normal)
+}
+
 impl Add for Tuple {
     type Output = Self;
     fn add(self, other: Tuple) -> Tuple {
@@ -198,6 +208,17 @@ impl Mul<f64> for Color {
     }
 }
 
+impl Sub for Color {
+    type Output = Self;
+    fn sub(self, other: Color) -> Color {
+        Color {
+            red: self.red - other.red,
+            green: self.green - other.green,
+            blue: self.blue - other.blue,
+        }
+    }
+}
+
 impl Mul<Color> for Color {
     type Output = Self;
     fn mul(self, rhs: Color) -> Self::Output {
@@ -213,94 +234,734 @@ pub fn color(red: f64, green: f64, blue: f64) -> Color {
     Color { red, green, blue }
 }
 
-pub struct Canvas {
-    pub pixels: Vec<Vec<Color>>,
-    pub width: i64,
-    pub height: i64,
+impl AbsDiffEq for Color {
+    type Epsilon = f64;
+    fn default_epsilon() -> f64 {
+        ERR
+    }
+    fn abs_diff_eq(&self, other: &Self, epsilon: f64) -> bool {
+        self.red.abs_diff_eq(&other.red, epsilon)
+            && self.green.abs_diff_eq(&other.green, epsilon)
+            && self.blue.abs_diff_eq(&other.blue, epsilon)
+    }
 }
 
-pub fn canvas(width: i64, height: i64) -> Canvas {
-    let mut pixels: Vec<Vec<Color>> = Vec::new();
-    // colunn-major order
-    for _ in 0..width {
-        let mut col: Vec<Color> = Vec::new();
-        for _ in 0..height {
-            col.push(color(0.0, 0.0, 0.0));
-        }
-        pixels.push(col)
+impl RelativeEq for Color {
+    fn default_max_relative() -> f64 {
+        ERR
     }
+    fn relative_eq(&self, other: &Self, epsilon: f64, max_relative: f64) -> bool {
+        self.red.relative_eq(&other.red, epsilon, max_relative)
+            && self.green.relative_eq(&other.green, epsilon, max_relative)
+            && self.blue.relative_eq(&other.blue, epsilon, max_relative)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    // row-major order: the pixel at (x, y) is at y * width + x
+    pixels: Vec<Color>,
+    width: usize,
+    height: usize,
+}
+
+pub fn canvas(width: usize, height: usize) -> Canvas {
+    // the buffer must hold exactly width * height pixels for the bounds
+    // checks in get and set to be sound
+    let len = width
+        .checked_mul(height)
+        .expect("canvas dimensions overflow");
     Canvas {
-        pixels,
+        pixels: vec![color(0.0, 0.0, 0.0); len],
         width,
         height,
     }
 }
 
-pub fn write_pixel(canvas: &mut Canvas, x: i64, y: i64, color: Color) {
-    canvas.pixels[x as usize][y as usize] = color
+// the pixel a checked write was asked to change, outside the canvas
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OutOfBounds {
+    pub x: usize,
+    pub y: usize,
 }
 
-pub fn pixel_at(canvas: &mut Canvas, x: i64, y: i64) -> Color {
-    canvas.pixels[x as usize][y as usize]
+impl std::fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "pixel ({}, {}) is outside the canvas", self.x, self.y)
+    }
 }
 
-impl PartialEq for Canvas {
-    fn eq(&self, other: &Self) -> bool {
-        for x in 0..self.pixels.len() {
-            for y in 0..self.pixels[x].len() {
-                if self.pixels[x as usize][y as usize] != other.pixels[x as usize][y as usize] {
-                    return false;
-                }
-            }
+impl std::error::Error for OutOfBounds {}
+
+impl Canvas {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // every pixel, a row at a time from the top
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &[Color]> {
+        (0..self.height).map(move |y| &self.pixels[y * self.width..(y + 1) * self.width])
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y * self.width + x)
+        } else {
+            None
         }
-        true
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Color> {
+        self.index(x, y).map(|i| self.pixels[i])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) -> Result<(), OutOfBounds> {
+        let i = self.index(x, y).ok_or(OutOfBounds { x, y })?;
+        self.pixels[i] = color;
+        Ok(())
+    }
+}
+
+// panics if the pixel is outside the canvas; use Canvas::set to check
+pub fn write_pixel(canvas: &mut Canvas, x: usize, y: usize, color: Color) {
+    if let Err(e) = canvas.set(x, y, color) {
+        panic!("{}", e)
+    }
+}
+
+// panics if the pixel is outside the canvas; use Canvas::get to check
+pub fn pixel_at(canvas: &Canvas, x: usize, y: usize) -> Color {
+    match canvas.get(x, y) {
+        Some(c) => c,
+        None => panic!("{}", OutOfBounds { x, y }),
     }
 }
 
 pub fn canvas_to_ppm(c: Canvas) -> String {
-    let mut ppm = String::new();
-    let version = "P3";
-    let max_color = "255";
-    ppm.push_str(version);
-    ppm.push('\n');
-    ppm.push_str(&format!("{} {}", c.width, c.height));
-    ppm.push('\n');
-    ppm.push_str(max_color);
-    ppm.push('\n');
+    let mut ppm = Vec::new();
+    write_ppm(&c, PpmFormat::Plain, &mut ppm).expect("writing to a Vec can't fail");
+    String::from_utf8(ppm).expect("plain ppm is ascii")
+}
+
+// the two flavours of ppm: P3 writes each channel as ascii decimal,
+// P6 as a single raw byte
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PpmFormat {
+    Plain,
+    Raw,
+}
+
+// streams the canvas as a ppm image, one row at a time, so a large render
+// never has to be held in memory twice
+pub fn write_ppm<W: Write>(c: &Canvas, format: PpmFormat, out: &mut W) -> io::Result<()> {
+    let version = match format {
+        PpmFormat::Plain => "P3",
+        PpmFormat::Raw => "P6",
+    };
+    write!(out, "{}\n{} {}\n255\n", version, c.width, c.height)?;
+    for row in c.rows() {
+        match format {
+            PpmFormat::Plain => write_plain_ppm_row(row, out)?,
+            PpmFormat::Raw => {
+                let mut bytes = Vec::with_capacity(c.width * 3);
+                for pixel in row.iter() {
+                    for b in [pixel.red, pixel.green, pixel.blue].iter() {
+                        bytes.push(byte_clamp(*b) as u8);
+                    }
+                }
+                out.write_all(&bytes)?;
+            }
+        }
+    }
+    Ok(())
+}
 
+// plain ppm lines should not be longer than 70 characters
+fn write_plain_ppm_row<W: Write>(row: &[Color], out: &mut W) -> io::Result<()> {
     let mut tmp = String::new();
     let mut leading = true;
-    // row-major traversal of
-    // column-major matrix
-    for x in 0..c.height {
-        let mut row: Vec<Color> = Vec::new();
-        for col in c.pixels.iter() {
-            row.push(col[x as usize]);
+    for pixel in row.iter() {
+        let components = [pixel.red, pixel.green, pixel.blue];
+        for b in components.iter() {
+            let clamped = byte_clamp(*b);
+            let mut formatted = format!(" {}", clamped);
+            if leading {
+                formatted = format!("{}", clamped);
+                leading = false;
+            }
+            if tmp.len() + formatted.len() > 70 {
+                tmp.push('\n');
+                out.write_all(tmp.as_bytes())?;
+                tmp = String::new();
+                formatted = format!("{}", clamped);
+            }
+            tmp.push_str(formatted.as_str());
+        }
+    }
+    tmp.push('\n');
+    out.write_all(tmp.as_bytes())
+}
+
+#[derive(Debug)]
+pub enum PpmError {
+    Io(io::Error),
+    // the magic number was neither P3 nor P6
+    UnsupportedFormat,
+    // the width, height or maxval is missing or out of range
+    InvalidHeader,
+    // a plain sample that isn't a number, or any sample above maxval
+    InvalidSample,
+    // the data ended before every pixel was read
+    Truncated,
+}
+
+impl std::fmt::Display for PpmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PpmError::Io(e) => write!(f, "reading ppm: {}", e),
+            PpmError::UnsupportedFormat => write!(f, "not a P3 or P6 ppm file"),
+            PpmError::InvalidHeader => write!(f, "malformed ppm header"),
+            PpmError::InvalidSample => write!(f, "ppm sample is not a number up to maxval"),
+            PpmError::Truncated => write!(f, "ppm data ends before the last pixel"),
+        }
+    }
+}
+
+impl std::error::Error for PpmError {}
+
+impl From<io::Error> for PpmError {
+    fn from(e: io::Error) -> PpmError {
+        PpmError::Io(e)
+    }
+}
+
+// reads whitespace separated tokens, skipping # comments to the end of a line
+struct PpmTokens<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PpmTokens<'a> {
+    fn next_token(&mut self) -> Option<&'a [u8]> {
+        while self.pos < self.data.len() {
+            match self.data[self.pos] {
+                b'#' => {
+                    while self.pos < self.data.len() && self.data[self.pos] != b'\n' {
+                        self.pos += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+        let start = self.pos;
+        while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            None
+        } else {
+            Some(&self.data[start..self.pos])
+        }
+    }
+
+    fn next_number(&mut self) -> Option<Result<u32, ()>> {
+        self.next_token().map(|t| {
+            std::str::from_utf8(t)
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .ok_or(())
+        })
+    }
+}
+
+// loads a plain or raw ppm image, scaling every sample by maxval into 0..1
+pub fn read_ppm<R: Read>(input: &mut R) -> Result<Canvas, PpmError> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    let mut tokens = PpmTokens {
+        data: &data,
+        pos: 0,
+    };
+    let raw = match tokens.next_token() {
+        Some(b"P3") => false,
+        Some(b"P6") => true,
+        _ => return Err(PpmError::UnsupportedFormat),
+    };
+    let mut header = [0; 3];
+    for field in header.iter_mut() {
+        *field = match tokens.next_number() {
+            Some(Ok(n)) => n,
+            _ => return Err(PpmError::InvalidHeader),
+        };
+    }
+    let [width, height, maxval] = header;
+    if maxval == 0 || maxval > 65535 {
+        return Err(PpmError::InvalidHeader);
+    }
+    let samples = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or(PpmError::InvalidHeader)?;
+    // a header can claim far more samples than the file holds
+    let mut values = Vec::with_capacity(samples.min(data.len()));
+    if raw {
+        // exactly one whitespace byte separates the header from the data
+        let start = tokens.pos + 1;
+        let size = if maxval < 256 { 1 } else { 2 };
+        let body = data.get(start..).unwrap_or(&[]);
+        let needed = samples.checked_mul(size).ok_or(PpmError::InvalidHeader)?;
+        if body.len() < needed {
+            return Err(PpmError::Truncated);
+        }
+        for sample in body.chunks(size).take(samples) {
+            // wide samples are big-endian
+            values.push(sample.iter().fold(0, |acc, b| acc << 8 | *b as u32));
+        }
+    } else {
+        for _ in 0..samples {
+            match tokens.next_number() {
+                Some(Ok(n)) => values.push(n),
+                Some(Err(())) => return Err(PpmError::InvalidSample),
+                None => return Err(PpmError::Truncated),
+            }
+        }
+    }
+    if values.iter().any(|v| *v > maxval) {
+        return Err(PpmError::InvalidSample);
+    }
+    let mut c = canvas(width as usize, height as usize);
+    let scale = maxval as f64;
+    for (pixel, rgb) in c.pixels.iter_mut().zip(values.chunks(3)) {
+        *pixel = color(
+            rgb[0] as f64 / scale,
+            rgb[1] as f64 / scale,
+            rgb[2] as f64 / scale,
+        );
+    }
+    Ok(c)
+}
+
+// bits per channel in a png image
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PngDepth {
+    Eight,
+    Sixteen,
+}
+
+// writes the canvas as an rgb png. the image data is zlib wrapped but left
+// uncompressed, which every decoder reads and needs no deflate encoder.
+pub fn write_png<W: Write>(c: &Canvas, depth: PngDepth, out: &mut W) -> io::Result<()> {
+    out.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'])?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(c.width as u32).to_be_bytes());
+    header.extend_from_slice(&(c.height as u32).to_be_bytes());
+    let bits = match depth {
+        PngDepth::Eight => 8,
+        PngDepth::Sixteen => 16,
+    };
+    // truecolour, default compression and filtering, not interlaced
+    header.extend_from_slice(&[bits, 2, 0, 0, 0]);
+    write_png_chunk(out, b"IHDR", &header)?;
+
+    let mut scanlines = Vec::new();
+    for row in c.rows() {
+        // no filter on any line
+        scanlines.push(0);
+        for pixel in row.iter() {
+            for b in [pixel.red, pixel.green, pixel.blue].iter() {
+                match depth {
+                    PngDepth::Eight => scanlines.push(byte_clamp(*b) as u8),
+                    PngDepth::Sixteen => {
+                        let wide = (b * 65535.0).clamp(0.0, 65535.0).round() as u16;
+                        scanlines.extend_from_slice(&wide.to_be_bytes());
+                    }
+                }
+            }
+        }
+    }
+    write_png_chunk(out, b"IDAT", &zlib_stored(&scanlines))?;
+    write_png_chunk(out, b"IEND", &[])
+}
+
+fn write_png_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    // the checksum covers the chunk type as well as its data
+    let crc = crc32_update(crc32_update(0xffff_ffff, kind), data) ^ 0xffff_ffff;
+    out.write_all(&crc.to_be_bytes())
+}
+
+// a zlib stream holding the data in deflate's uncompressed blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32k window, and no preset dictionary
+    let mut z = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        z.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        z.push(last as u8);
+        let len = block.len() as u16;
+        z.extend_from_slice(&len.to_le_bytes());
+        z.extend_from_slice(&(!len).to_le_bytes());
+        z.extend_from_slice(block);
+    }
+    z.extend_from_slice(&adler32(data).to_be_bytes());
+    z
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for b in data.iter() {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data.iter() {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+#[derive(Debug)]
+pub enum HdrError {
+    Io(io::Error),
+    // not a colour pfm, or not an rgbe radiance file
+    UnsupportedFormat,
+    // the size, scale or resolution line is missing or malformed
+    InvalidHeader,
+    // the data ended before every pixel was read
+    Truncated,
+}
+
+impl std::fmt::Display for HdrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HdrError::Io(e) => write!(f, "reading hdr image: {}", e),
+            HdrError::UnsupportedFormat => write!(f, "not a colour pfm or rgbe hdr file"),
+            HdrError::InvalidHeader => write!(f, "malformed hdr image header"),
+            HdrError::Truncated => write!(f, "hdr image data ends before the last pixel"),
         }
+    }
+}
+
+impl std::error::Error for HdrError {}
+
+impl From<io::Error> for HdrError {
+    fn from(e: io::Error) -> HdrError {
+        HdrError::Io(e)
+    }
+}
+
+// writes the unclamped colours as a little-endian portable float map
+pub fn write_pfm<W: Write>(c: &Canvas, out: &mut W) -> io::Result<()> {
+    // a negative scale marks the samples as little-endian
+    write!(out, "PF\n{} {}\n-1.0\n", c.width, c.height)?;
+    let mut bytes = Vec::with_capacity(c.width * 12);
+    // rows run from the bottom of the image up
+    for row in c.rows().rev() {
+        bytes.clear();
         for pixel in row.iter() {
-            let components = [pixel.red, pixel.green, pixel.blue];
-            for b in components.iter() {
-                let clamped = byte_clamp(*b);
-                let mut formatted = format!(" {}", clamped);
-                if leading {
-                    formatted = format!("{}", clamped);
-                    leading = false;
+            for v in [pixel.red, pixel.green, pixel.blue].iter() {
+                bytes.extend_from_slice(&(*v as f32).to_le_bytes());
+            }
+        }
+        out.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+pub fn read_pfm<R: Read>(input: &mut R) -> Result<Canvas, HdrError> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    let mut tokens = PpmTokens {
+        data: &data,
+        pos: 0,
+    };
+    if tokens.next_token() != Some(b"PF") {
+        return Err(HdrError::UnsupportedFormat);
+    }
+    let (width, height) = match (tokens.next_number(), tokens.next_number()) {
+        (Some(Ok(w)), Some(Ok(h))) => (w as usize, h as usize),
+        _ => return Err(HdrError::InvalidHeader),
+    };
+    let scale: f64 = match tokens
+        .next_token()
+        .and_then(|t| std::str::from_utf8(t).ok())
+    {
+        Some(t) => t.parse().map_err(|_| HdrError::InvalidHeader)?,
+        None => return Err(HdrError::InvalidHeader),
+    };
+    if scale == 0.0 || !scale.is_finite() {
+        return Err(HdrError::InvalidHeader);
+    }
+    // exactly one whitespace byte separates the header from the data
+    let body = data.get(tokens.pos + 1..).unwrap_or(&[]);
+    let pixels = width.checked_mul(height).ok_or(HdrError::InvalidHeader)?;
+    if body.len() < pixels.checked_mul(12).ok_or(HdrError::InvalidHeader)? {
+        return Err(HdrError::Truncated);
+    }
+    let mut c = canvas(width, height);
+    for (i, rgb) in body.chunks(12).take(pixels).enumerate() {
+        let mut channels = [0.0; 3];
+        for (channel, bytes) in channels.iter_mut().zip(rgb.chunks(4)) {
+            let mut b = [0; 4];
+            b.copy_from_slice(bytes);
+            *channel = if scale < 0.0 {
+                f32::from_le_bytes(b)
+            } else {
+                f32::from_be_bytes(b)
+            } as f64;
+        }
+        let (x, y) = (i % width, height - 1 - i / width);
+        write_pixel(&mut c, x, y, color(channels[0], channels[1], channels[2]));
+    }
+    Ok(c)
+}
+
+// a shared exponent byte followed by three mantissa bytes, as radiance
+// stores them. channels below zero can't be represented and become zero.
+fn color_to_rgbe(c: Color) -> [u8; 4] {
+    let v = c.red.max(c.green).max(c.blue);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    let mut m = v / 2f64.powi(e);
+    if m >= 1.0 {
+        m /= 2.0;
+        e += 1;
+    } else if m < 0.5 {
+        m *= 2.0;
+        e -= 1;
+    }
+    let scale = m * 256.0 / v;
+    let channel = |x: f64| (x * scale).max(0.0) as u8;
+    [
+        channel(c.red),
+        channel(c.green),
+        channel(c.blue),
+        (e + 128).clamp(0, 255) as u8,
+    ]
+}
+
+fn rgbe_to_color(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return color(0.0, 0.0, 0.0);
+    }
+    let f = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    // the centre of each mantissa step, rather than its floor
+    let channel = |b: u8| (b as f64 + 0.5) * f;
+    color(channel(rgbe[0]), channel(rgbe[1]), channel(rgbe[2]))
+}
+
+// writes the canvas as a radiance rgbe image, each scanline run-length
+// encoded a channel at a time
+pub fn write_hdr<W: Write>(c: &Canvas, out: &mut W) -> io::Result<()> {
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        c.height, c.width
+    )?;
+    let width = c.width;
+    let mut scanline = Vec::with_capacity(width * 4);
+    let mut channel = Vec::with_capacity(width);
+    for row in c.rows() {
+        scanline.clear();
+        let pixels: Vec<[u8; 4]> = row.iter().map(|p| color_to_rgbe(*p)).collect();
+        // the encoding can only describe scanlines of these widths
+        if !(8..=0x7fff).contains(&width) {
+            for p in pixels.iter() {
+                scanline.extend_from_slice(p);
+            }
+        } else {
+            scanline.extend_from_slice(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8]);
+            for i in 0..4 {
+                channel.clear();
+                channel.extend(pixels.iter().map(|p| p[i]));
+                rle_encode(&channel, &mut scanline);
+            }
+        }
+        out.write_all(&scanline)?;
+    }
+    Ok(())
+}
+
+// a count above 128 repeats the next byte count - 128 times, anything
+// else is followed by that many literal bytes
+fn rle_encode(data: &[u8], out: &mut Vec<u8>) {
+    let mut pos = 0;
+    while pos < data.len() {
+        // find the next run worth encoding, no further than a literal
+        // block can reach
+        let mut run_start = pos;
+        let mut run_len = 0;
+        while run_start < data.len() && run_start - pos < 128 {
+            run_len = data[run_start..]
+                .iter()
+                .take(127)
+                .take_while(|b| **b == data[run_start])
+                .count();
+            if run_len >= 3 {
+                break;
+            }
+            run_start += 1;
+        }
+        if run_len < 3 {
+            run_start = data.len().min(pos + 128);
+        }
+        if run_start > pos {
+            out.push((run_start - pos) as u8);
+            out.extend_from_slice(&data[pos..run_start]);
+            pos = run_start;
+        } else {
+            out.push(128 + run_len as u8);
+            out.push(data[pos]);
+            pos += run_len;
+        }
+    }
+}
+
+pub fn read_hdr<R: Read>(input: &mut R) -> Result<Canvas, HdrError> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    let mut lines = data.split(|b| *b == b'\n');
+    let mut header_len = 0;
+    let mut next_line = || lines.next().inspect(|l| header_len += l.len() + 1);
+    match next_line() {
+        Some(b"#?RADIANCE") | Some(b"#?RGBE") => (),
+        _ => return Err(HdrError::UnsupportedFormat),
+    }
+    // variables run until a blank line
+    loop {
+        match next_line() {
+            Some(b"") => break,
+            Some(line) if line.starts_with(b"FORMAT=") => {
+                if line != b"FORMAT=32-bit_rle_rgbe" {
+                    return Err(HdrError::UnsupportedFormat);
                 }
-                if tmp.len() + formatted.len() > 70 {
-                    tmp.push('\n');
-                    ppm.push_str(tmp.as_str());
-                    tmp = String::new();
-                    formatted = format!("{}", clamped);
+            }
+            Some(_) => (),
+            None => return Err(HdrError::InvalidHeader),
+        }
+    }
+    // only the usual top-to-bottom, left-to-right orientation
+    let resolution = next_line()
+        .and_then(|l| std::str::from_utf8(l).ok())
+        .ok_or(HdrError::InvalidHeader)?;
+    let fields: Vec<&str> = resolution.split_whitespace().collect();
+    let (height, width) = match fields.as_slice() {
+        ["-Y", h, "+X", w] => match (h.parse::<usize>(), w.parse::<usize>()) {
+            (Ok(h), Ok(w)) => (h, w),
+            _ => return Err(HdrError::InvalidHeader),
+        },
+        _ => return Err(HdrError::InvalidHeader),
+    };
+    let mut body = data.get(header_len..).unwrap_or(&[]);
+    // check the claimed size against the data before allocating for it.
+    // every scanline takes at least one four byte pixel. legacy repeats can
+    // describe a row of any width in a few bytes, so rather than trust the
+    // header, rows wider than 128 pixels per byte of data are rejected.
+    width.checked_mul(height).ok_or(HdrError::InvalidHeader)?;
+    let min_len = height.checked_mul(4).ok_or(HdrError::InvalidHeader)?;
+    if body.len() < min_len || body.len().saturating_mul(128) < width {
+        return Err(HdrError::Truncated);
+    }
+    // the image grows a row at a time, so a file that runs out of data
+    // fails before the whole canvas is allocated
+    let mut pixels = Vec::new();
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        body = read_hdr_scanline(body, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|rgbe| rgbe_to_color(*rgbe)));
+    }
+    Ok(Canvas {
+        pixels,
+        width,
+        height,
+    })
+}
+
+// fills the scanline from the front of data, returning what follows it
+fn read_hdr_scanline<'a>(
+    mut data: &'a [u8],
+    scanline: &mut [[u8; 4]],
+) -> Result<&'a [u8], HdrError> {
+    let width = scanline.len();
+    let rle = (8..=0x7fff).contains(&width) && data.len() >= 4 && data[0] == 2 && data[1] == 2;
+    if rle {
+        if ((data[2] as usize) << 8 | data[3] as usize) != width {
+            return Err(HdrError::InvalidHeader);
+        }
+        data = &data[4..];
+        for i in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = *data.first().ok_or(HdrError::Truncated)? as usize;
+                if count > 128 {
+                    let value = *data.get(1).ok_or(HdrError::Truncated)?;
+                    for p in scanline.iter_mut().skip(x).take(count - 128) {
+                        p[i] = value;
+                    }
+                    x += count - 128;
+                    data = &data[2..];
+                } else {
+                    let literal = data.get(1..1 + count).ok_or(HdrError::Truncated)?;
+                    for (p, value) in scanline.iter_mut().skip(x).zip(literal.iter()) {
+                        p[i] = *value;
+                    }
+                    x += count;
+                    data = &data[1 + count..];
                 }
-                tmp.push_str(formatted.as_str());
             }
         }
-        tmp.push('\n');
-        leading = true;
-        ppm.push_str(tmp.as_str());
-        tmp = String::new();
+        return Ok(data);
+    }
+    // flat pixels, where 1 1 1 n repeats the previous pixel n times,
+    // with successive repeats shifting n up by a byte
+    let mut x = 0;
+    let mut shift = 0;
+    while x < width {
+        let p = data.get(..4).ok_or(HdrError::Truncated)?;
+        data = &data[4..];
+        if p[0] == 1 && p[1] == 1 && p[2] == 1 && x > 0 {
+            let previous = scanline[x - 1];
+            let count = (p[3] as usize).checked_shl(shift).unwrap_or(width);
+            for q in scanline.iter_mut().skip(x).take(count) {
+                *q = previous;
+            }
+            x += count;
+            shift += 8;
+        } else {
+            scanline[x].copy_from_slice(p);
+            x += 1;
+            shift = 0;
+        }
     }
-    ppm
+    Ok(data)
 }
 
 pub fn byte_clamp(x: f64) -> i64 {
@@ -344,7 +1005,7 @@ pub fn scaling(x: f64, y: f64, z: f64) -> Matrix4<f64> {
 pub fn rotation_x(angle: f64) -> Matrix4<f64> {
     let mut m = Matrix4::new_scaling(1.0);
     m[(1, 1)] = angle.cos();
-    m[(1, 2)] = -1.0 * angle.sin();
+    m[(1, 2)] = -angle.sin();
     m[(2, 2)] = angle.cos();
     m[(2, 1)] = angle.sin();
     m
@@ -353,7 +1014,7 @@ pub fn rotation_x(angle: f64) -> Matrix4<f64> {
 pub fn rotation_y(angle: f64) -> Matrix4<f64> {
     let mut m = Matrix4::new_scaling(1.0);
     m[(0, 0)] = angle.cos();
-    m[(2, 0)] = angle.sin();
+    m[(2, 0)] = -angle.sin();
     m[(0, 2)] = angle.sin();
     m[(2, 2)] = angle.cos();
     m
@@ -362,7 +1023,7 @@ pub fn rotation_y(angle: f64) -> Matrix4<f64> {
 pub fn rotation_z(angle: f64) -> Matrix4<f64> {
     let mut m = Matrix4::new_scaling(1.0);
     m[(0, 0)] = angle.cos();
-    m[(0, 1)] = -1.0 * angle.sin();
+    m[(0, 1)] = -angle.sin();
     m[(1, 0)] = angle.sin();
     m[(1, 1)] = angle.cos();
     m[(2, 2)] = 1.0;
@@ -385,688 +1046,5733 @@ pub fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Matrix4
     m
 }
 
+impl Mul<Tuple> for Matrix4<f64> {
+    type Output = Tuple;
+    fn mul(self, rhs: Tuple) -> Tuple {
+        let v = self * Vector4::new(rhs.x, rhs.y, rhs.z, rhs.w);
+        Tuple::new(v[0], v[1], v[2], v[3])
+    }
+}
 
-#[cfg(test)]
-#[macro_use]
-extern crate approx;
-mod tests {
-    use super::*;
-    use na::{Dynamic, Matrix, Point3, VecStorage, Vector3};
+impl AbsDiffEq for Tuple {
+    type Epsilon = f64;
+    fn default_epsilon() -> f64 {
+        ERR
+    }
+    fn abs_diff_eq(&self, other: &Self, epsilon: f64) -> bool {
+        self.x.abs_diff_eq(&other.x, epsilon)
+            && self.y.abs_diff_eq(&other.y, epsilon)
+            && self.z.abs_diff_eq(&other.z, epsilon)
+            && self.w.abs_diff_eq(&other.w, epsilon)
+    }
+}
 
-    // initial tests
-    #[test]
-    fn test_page30() {
-        assert_eq!(
-            Tuple {
-                x: 1.0,
-                y: 2.0,
-                z: 3.0,
-                w: 4.0
-            },
-            Tuple {
-                x: 1.0,
-                y: 2.0,
-                z: 3.0,
-                w: 4.0
-            }
-        );
+impl RelativeEq for Tuple {
+    fn default_max_relative() -> f64 {
+        ERR
+    }
+    fn relative_eq(&self, other: &Self, epsilon: f64, max_relative: f64) -> bool {
+        self.x.relative_eq(&other.x, epsilon, max_relative)
+            && self.y.relative_eq(&other.y, epsilon, max_relative)
+            && self.z.relative_eq(&other.z, epsilon, max_relative)
+            && self.w.relative_eq(&other.w, epsilon, max_relative)
+    }
+}
 
-        let p = Tuple::new(4.30, -4.2, 3.1, 1.0);
-        assert_eq!(p.x, 4.3);
-        assert_eq!(p.y, -4.2);
-        assert_eq!(p.z, 3.1);
-        assert_eq!(p.w, 1.0);
-        assert_eq!(p.is_point(), true);
-        assert_eq!(p.is_vector(), false);
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Tuple,
+    pub direction: Tuple,
+}
 
-        let p = Tuple::new(4.30, -4.2, 3.1, 0.0);
-        assert_eq!(p.x, 4.3);
-        assert_eq!(p.y, -4.2);
-        assert_eq!(p.z, 3.1);
-        assert_eq!(p.w, 0.0);
-        assert_eq!(p.is_point(), false);
-        assert_eq!(p.is_vector(), true);
+pub fn ray(origin: Tuple, direction: Tuple) -> Ray {
+    Ray { origin, direction }
+}
+
+impl Ray {
+    // the point t units along the ray
+    pub fn position(&self, t: f64) -> Tuple {
+        self.origin + self.direction * t
     }
+    // origin is a point (w=1) so it is translated,
+    // direction is a vector (w=0) so it is not
+    pub fn transform(&self, m: &Matrix4<f64>) -> Ray {
+        Ray {
+            origin: *m * self.origin,
+            direction: *m * self.direction,
+        }
+    }
+}
 
-    #[test]
-    fn test_point() {
-        let p = Tuple::point(4.0, -4.0, 3.0);
-        assert_eq!(p, Tuple::new(4.0, -4.0, 3.0, 1.0))
+// anything that can be placed in a world. implementors only deal with
+// their own object space; moving rays and normals between world and
+// object space is shared by the provided methods.
+pub trait Shape: Debug {
+    fn transform(&self) -> &Matrix4<f64>;
+    fn material(&self) -> &Material;
+    // the combined transform of every group the shape sits inside, so the
+    // shape can reach world space without walking back up through them.
+    // identity for a shape placed directly in a world.
+    fn parent_transform(&self) -> &Matrix4<f64>;
+    // called by the group that takes ownership of the shape
+    fn set_parent_transform(&mut self, m: Matrix4<f64>);
+    // the ray has already been moved into object space
+    fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>>;
+    // the point is in object space, and so is the returned normal.
+    // `hit` is the intersection that found the point.
+    fn local_normal_at(&self, p: Tuple, hit: &Intersection) -> Tuple;
+    // an axis-aligned box around the shape, in object space
+    fn bounds(&self) -> BoundingBox;
+
+    // the shape's bounds in the space of whatever contains it
+    fn parent_space_bounds(&self) -> BoundingBox {
+        self.bounds().transform(self.transform())
     }
 
-    #[test]
-    fn test_comparison() {
-        let a = Tuple::new(1.0, 2.0, 3.0, 4.0);
-        let b = Tuple::new(1.0, 2.0, 3.0, 4.0 + (ERR / 2.0));
-        assert!(Tuple::eq(a, b.clone()));
-        let c = Tuple::new(4.0, 3.0, 2.0, 1.0);
-        assert!(Tuple::eq(b, c));
+    // splits shapes made of many others into a hierarchy of groups
+    // with at most threshold direct children each. leaf shapes have
+    // nothing to split.
+    fn divide(&mut self, _threshold: usize) {}
+
+    fn intersect(&self, r: &Ray) -> Intersections<'_> {
+        // a singular transform flattens the shape away to nothing
+        let inv = match self.transform().try_inverse() {
+            Some(inv) => inv,
+            None => return intersections(vec![]),
+        };
+        // move the ray into object space rather than the shape into world space
+        intersections(self.local_intersect(&r.transform(&inv)))
     }
 
-    // adding two tuples
-    #[test]
-    fn test_add() {
-        let a = Tuple::new(3.0, -2.0, 5.0, 1.0);
-        let b = Tuple::new(-2.0, 3.0, 1.0, 0.0);
-        assert_eq!(a + b, Tuple::new(1.0, 1.0, 6.0, 1.0));
+    // the surface normal at a point on the shape, in world space
+    fn normal_at(&self, world_point: Tuple, hit: &Intersection) -> Tuple {
+        let local_normal = self.local_normal_at(self.world_to_object(world_point), hit);
+        self.normal_to_world(local_normal)
     }
 
-    // subtracing two points
-    #[test]
-    fn test_sub() {
-        let p1 = Tuple::point(3.0, 2.0, 1.0);
-        let p2 = Tuple::point(5.0, 6.0, 7.0);
-        assert_eq!(p1 - p2, Tuple::vector(-2.0, -4.0, -6.0));
+    // the inverse of every transform from the world down to this shape
+    fn world_to_object_transform(&self) -> Matrix4<f64> {
+        (self.parent_transform() * self.transform())
+            .try_inverse()
+            .expect("a shape with a singular transform has no surface")
+    }
+
+    // moves a world space point through every parent group into object space
+    fn world_to_object(&self, p: Tuple) -> Tuple {
+        self.world_to_object_transform() * p
+    }
+
+    // whether other is this shape or, for shapes made of other shapes,
+    // one of its descendants
+    fn includes(&self, other: &dyn Shape) -> bool {
+        std::ptr::eq(
+            self as *const Self as *const u8,
+            other as *const dyn Shape as *const u8,
+        )
+    }
+
+    // moves an object space normal through every parent group into world space
+    fn normal_to_world(&self, n: Tuple) -> Tuple {
+        // normals are not points: the inverse transpose keeps them
+        // perpendicular to the surface under non-uniform scaling
+        let mut world_normal = self.world_to_object_transform().transpose() * n;
+        // the transpose drags the translation into w, so discard it
+        world_normal.w = 0.0;
+        normalize(world_normal)
+    }
+}
+
+// shapes are compared by identity: two identical spheres at different
+// addresses are still different objects in the world
+impl<'a> PartialEq for dyn Shape + 'a {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(
+            self as *const Self as *const u8,
+            other as *const Self as *const u8,
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sphere {
+    pub transform: Matrix4<f64>,
+    parent_transform: Matrix4<f64>,
+    pub material: Material,
+}
+
+// a unit sphere centred on the origin
+pub fn sphere() -> Sphere {
+    Sphere {
+        transform: Matrix4::identity(),
+        parent_transform: Matrix4::identity(),
+        material: material(),
+    }
+}
+
+impl Shape for Sphere {
+    fn transform(&self) -> &Matrix4<f64> {
+        &self.transform
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn parent_transform(&self) -> &Matrix4<f64> {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, m: Matrix4<f64>) {
+        self.parent_transform = m;
+    }
+
+    // a tangent ray crosses at the same t twice
+    fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>> {
+        let sphere_to_ray = r.origin - Tuple::point(0.0, 0.0, 0.0);
+        let a = dot(r.direction, r.direction);
+        let b = 2.0 * dot(r.direction, sphere_to_ray);
+        let c = dot(sphere_to_ray, sphere_to_ray) - 1.0;
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return vec![];
+        }
+        let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
+        let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
+        vec![intersection(t1, self), intersection(t2, self)]
+    }
+
+    fn local_normal_at(&self, p: Tuple, _hit: &Intersection) -> Tuple {
+        p - Tuple::point(0.0, 0.0, 0.0)
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        bounding_box(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0))
+    }
+}
+
+// the infinite xz plane through the origin
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Plane {
+    pub transform: Matrix4<f64>,
+    parent_transform: Matrix4<f64>,
+    pub material: Material,
+}
+
+pub fn plane() -> Plane {
+    Plane {
+        transform: Matrix4::identity(),
+        parent_transform: Matrix4::identity(),
+        material: material(),
+    }
+}
+
+impl Shape for Plane {
+    fn transform(&self) -> &Matrix4<f64> {
+        &self.transform
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn parent_transform(&self) -> &Matrix4<f64> {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, m: Matrix4<f64>) {
+        self.parent_transform = m;
+    }
+
+    fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>> {
+        // a ray parallel to the plane never meets it, and a coplanar
+        // ray would only ever see its infinitely thin edge. the tolerance
+        // is relative to the direction, which shrinks as the plane is
+        // scaled up.
+        if r.direction.y.abs() < ERR * magnitude(r.direction) {
+            return vec![];
+        }
+        let t = -r.origin.y / r.direction.y;
+        vec![intersection(t, self)]
+    }
+
+    fn local_normal_at(&self, _p: Tuple, _hit: &Intersection) -> Tuple {
+        Tuple::vector(0.0, 1.0, 0.0)
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        bounding_box(
+            Tuple::point(-f64::INFINITY, 0.0, -f64::INFINITY),
+            Tuple::point(f64::INFINITY, 0.0, f64::INFINITY),
+        )
+    }
+}
+
+// where a ray enters and leaves the slab between two planes perpendicular
+// to one axis, given the ray's origin and direction along that axis
+fn check_axis(origin: f64, direction: f64, min: f64, max: f64) -> (f64, f64) {
+    let tmin_numerator = min - origin;
+    let tmax_numerator = max - origin;
+    // a ray parallel to the slab is either always or never inside it. only
+    // an exact zero counts: the direction shrinks as a shape is scaled up.
+    let (tmin, tmax) = if direction != 0.0 {
+        (tmin_numerator / direction, tmax_numerator / direction)
+    } else {
+        (
+            tmin_numerator * f64::INFINITY,
+            tmax_numerator * f64::INFINITY,
+        )
+    };
+    if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+// the t values where the ray enters and leaves the axis-aligned box
+// spanning the points min and max, or None if it misses
+fn box_intersect(r: &Ray, min: Tuple, max: Tuple) -> Option<(f64, f64)> {
+    let (xtmin, xtmax) = check_axis(r.origin.x, r.direction.x, min.x, max.x);
+    let (ytmin, ytmax) = check_axis(r.origin.y, r.direction.y, min.y, max.y);
+    let (ztmin, ztmax) = check_axis(r.origin.z, r.direction.z, min.z, max.z);
+    // the ray is inside the box only where it is inside all three slabs
+    let tmin = xtmin.max(ytmin).max(ztmin);
+    let tmax = xtmax.min(ytmax).min(ztmax);
+    if tmin > tmax {
+        None
+    } else {
+        Some((tmin, tmax))
+    }
+}
+
+// an axis-aligned box between two corner points. an empty box has its
+// minimum above its maximum, so that adding anything to it replaces both.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingBox {
+    pub min: Tuple,
+    pub max: Tuple,
+}
+
+pub fn bounding_box(min: Tuple, max: Tuple) -> BoundingBox {
+    BoundingBox { min, max }
+}
+
+pub fn empty_bounding_box() -> BoundingBox {
+    bounding_box(
+        Tuple::point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        Tuple::point(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY),
+    )
+}
+
+impl BoundingBox {
+    pub fn add_point(&mut self, p: Tuple) {
+        self.min = Tuple::point(
+            self.min.x.min(p.x),
+            self.min.y.min(p.y),
+            self.min.z.min(p.z),
+        );
+        self.max = Tuple::point(
+            self.max.x.max(p.x),
+            self.max.y.max(p.y),
+            self.max.z.max(p.z),
+        );
+    }
+
+    pub fn add_box(&mut self, other: &BoundingBox) {
+        self.add_point(other.min);
+        self.add_point(other.max);
+    }
+
+    pub fn contains_point(&self, p: Tuple) -> bool {
+        (self.min.x..=self.max.x).contains(&p.x)
+            && (self.min.y..=self.max.y).contains(&p.y)
+            && (self.min.z..=self.max.z).contains(&p.z)
+    }
+
+    pub fn contains_box(&self, other: &BoundingBox) -> bool {
+        self.contains_point(other.min) && self.contains_point(other.max)
+    }
+
+    // the box around all eight transformed corners of this one
+    pub fn transform(&self, m: &Matrix4<f64>) -> BoundingBox {
+        let (lo, hi) = (self.min, self.max);
+        let corners = [
+            Tuple::point(lo.x, lo.y, lo.z),
+            Tuple::point(lo.x, lo.y, hi.z),
+            Tuple::point(lo.x, hi.y, lo.z),
+            Tuple::point(lo.x, hi.y, hi.z),
+            Tuple::point(hi.x, lo.y, lo.z),
+            Tuple::point(hi.x, lo.y, hi.z),
+            Tuple::point(hi.x, hi.y, lo.z),
+            Tuple::point(hi.x, hi.y, hi.z),
+        ];
+        let mut b = empty_bounding_box();
+        for c in corners.iter() {
+            let p = [c.x, c.y, c.z, c.w];
+            let mut out = [0.0; 3];
+            for (row, o) in out.iter_mut().enumerate() {
+                // an infinite corner would turn every zero in the matrix
+                // into a NaN, so zeros are skipped rather than multiplied
+                *o = (0..4)
+                    .filter(|&col| m[(row, col)] != 0.0)
+                    .map(|col| m[(row, col)] * p[col])
+                    .sum();
+            }
+            b.add_point(Tuple::point(out[0], out[1], out[2]));
+            // opposite infinities cancel to NaN, which min and max ignore,
+            // but such an axis is unbounded both ways
+            if out[0].is_nan() {
+                b.min.x = -f64::INFINITY;
+                b.max.x = f64::INFINITY;
+            }
+            if out[1].is_nan() {
+                b.min.y = -f64::INFINITY;
+                b.max.y = f64::INFINITY;
+            }
+            if out[2].is_nan() {
+                b.min.z = -f64::INFINITY;
+                b.max.z = f64::INFINITY;
+            }
+        }
+        b
+    }
+
+    pub fn intersects(&self, r: &Ray) -> bool {
+        box_intersect(r, self.min, self.max).is_some()
+    }
+
+    // cuts the box in half across its longest axis
+    pub fn split(&self) -> (BoundingBox, BoundingBox) {
+        let dx = self.max.x - self.min.x;
+        let dy = self.max.y - self.min.y;
+        let dz = self.max.z - self.min.z;
+        let (mut x0, mut y0, mut z0) = (self.min.x, self.min.y, self.min.z);
+        let (mut x1, mut y1, mut z1) = (self.max.x, self.max.y, self.max.z);
+        let greatest = dx.max(dy).max(dz);
+        if greatest <= dx {
+            x0 += dx / 2.0;
+            x1 = x0;
+        } else if greatest <= dy {
+            y0 += dy / 2.0;
+            y1 = y0;
+        } else {
+            z0 += dz / 2.0;
+            z1 = z0;
+        }
+        (
+            bounding_box(self.min, Tuple::point(x1, y1, z1)),
+            bounding_box(Tuple::point(x0, y0, z0), self.max),
+        )
+    }
+}
+
+// an axis-aligned cube spanning -1 to 1 on every axis
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cube {
+    pub transform: Matrix4<f64>,
+    parent_transform: Matrix4<f64>,
+    pub material: Material,
+}
+
+pub fn cube() -> Cube {
+    Cube {
+        transform: Matrix4::identity(),
+        parent_transform: Matrix4::identity(),
+        material: material(),
+    }
+}
+
+impl Shape for Cube {
+    fn transform(&self) -> &Matrix4<f64> {
+        &self.transform
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn parent_transform(&self) -> &Matrix4<f64> {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, m: Matrix4<f64>) {
+        self.parent_transform = m;
+    }
+
+    fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>> {
+        let min = Tuple::point(-1.0, -1.0, -1.0);
+        let max = Tuple::point(1.0, 1.0, 1.0);
+        match box_intersect(r, min, max) {
+            Some((tmin, tmax)) => vec![intersection(tmin, self), intersection(tmax, self)],
+            None => vec![],
+        }
+    }
+
+    // the face a point lies on is the one its largest component points at
+    #[allow(clippy::float_cmp)]
+    fn local_normal_at(&self, p: Tuple, _hit: &Intersection) -> Tuple {
+        let maxc = p.x.abs().max(p.y.abs()).max(p.z.abs());
+        if maxc == p.x.abs() {
+            Tuple::vector(p.x, 0.0, 0.0)
+        } else if maxc == p.y.abs() {
+            Tuple::vector(0.0, p.y, 0.0)
+        } else {
+            Tuple::vector(0.0, 0.0, p.z)
+        }
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        bounding_box(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0))
+    }
+}
+
+// an infinitely long cylinder of radius 1 around the y axis, optionally
+// truncated to lie between minimum and maximum (exclusive) on y
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cylinder {
+    pub transform: Matrix4<f64>,
+    parent_transform: Matrix4<f64>,
+    pub material: Material,
+    pub minimum: f64,
+    pub maximum: f64,
+    // whether a truncated cylinder has end caps
+    pub closed: bool,
+}
+
+pub fn cylinder() -> Cylinder {
+    Cylinder {
+        transform: Matrix4::identity(),
+        parent_transform: Matrix4::identity(),
+        material: material(),
+        minimum: -f64::INFINITY,
+        maximum: f64::INFINITY,
+        closed: false,
+    }
+}
+
+// whether the ray at t is within the given radius of the y axis
+fn check_cap(r: &Ray, t: f64, radius: f64) -> bool {
+    let x = r.origin.x + t * r.direction.x;
+    let z = r.origin.z + t * r.direction.z;
+    x * x + z * z <= radius * radius
+}
+
+// the roots of the quadratic at^2 + bt + c for the walls of a cylinder or
+// cone, in increasing order, keeping only those between minimum and maximum
+fn truncated_roots(r: &Ray, a: f64, b: f64, c: f64, minimum: f64, maximum: f64) -> Vec<f64> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    let mut t0 = (-b - discriminant.sqrt()) / (2.0 * a);
+    let mut t1 = (-b + discriminant.sqrt()) / (2.0 * a);
+    if t0 > t1 {
+        std::mem::swap(&mut t0, &mut t1);
+    }
+    [t0, t1]
+        .iter()
+        .cloned()
+        .filter(|t| {
+            let y = r.origin.y + t * r.direction.y;
+            minimum < y && y < maximum
+        })
+        .collect()
+}
+
+impl Shape for Cylinder {
+    fn transform(&self) -> &Matrix4<f64> {
+        &self.transform
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn parent_transform(&self) -> &Matrix4<f64> {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, m: Matrix4<f64>) {
+        self.parent_transform = m;
+    }
+
+    fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>> {
+        let mut xs = vec![];
+        let a = r.direction.x * r.direction.x + r.direction.z * r.direction.z;
+        // a ray parallel to the y axis can only hit the caps. the direction
+        // shrinks as the cylinder is scaled up, so the tolerance must too.
+        if a.abs() >= f64::EPSILON * dot(r.direction, r.direction) {
+            let b = 2.0 * (r.origin.x * r.direction.x + r.origin.z * r.direction.z);
+            let c = r.origin.x * r.origin.x + r.origin.z * r.origin.z - 1.0;
+            for t in truncated_roots(r, a, b, c, self.minimum, self.maximum) {
+                xs.push(intersection(t, self));
+            }
+        }
+        if self.closed && r.direction.y != 0.0 {
+            for cap in [self.minimum, self.maximum].iter() {
+                let t = (cap - r.origin.y) / r.direction.y;
+                if check_cap(r, t, 1.0) {
+                    xs.push(intersection(t, self));
+                }
+            }
+        }
+        xs
+    }
+
+    fn local_normal_at(&self, p: Tuple, _hit: &Intersection) -> Tuple {
+        let dist = p.x * p.x + p.z * p.z;
+        if dist < 1.0 && p.y >= self.maximum - ERR {
+            Tuple::vector(0.0, 1.0, 0.0)
+        } else if dist < 1.0 && p.y <= self.minimum + ERR {
+            Tuple::vector(0.0, -1.0, 0.0)
+        } else {
+            Tuple::vector(p.x, 0.0, p.z)
+        }
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        bounding_box(
+            Tuple::point(-1.0, self.minimum, -1.0),
+            Tuple::point(1.0, self.maximum, 1.0),
+        )
+    }
+}
+
+// a double-napped cone around the y axis with its tips meeting at the
+// origin, where the radius at any y is |y|. truncated like a cylinder.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cone {
+    pub transform: Matrix4<f64>,
+    parent_transform: Matrix4<f64>,
+    pub material: Material,
+    pub minimum: f64,
+    pub maximum: f64,
+    pub closed: bool,
+}
+
+pub fn cone() -> Cone {
+    Cone {
+        transform: Matrix4::identity(),
+        parent_transform: Matrix4::identity(),
+        material: material(),
+        minimum: -f64::INFINITY,
+        maximum: f64::INFINITY,
+        closed: false,
+    }
+}
+
+impl Shape for Cone {
+    fn transform(&self) -> &Matrix4<f64> {
+        &self.transform
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn parent_transform(&self) -> &Matrix4<f64> {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, m: Matrix4<f64>) {
+        self.parent_transform = m;
+    }
+
+    fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>> {
+        let mut xs = vec![];
+        let (o, d) = (r.origin, r.direction);
+        let a = d.x * d.x - d.y * d.y + d.z * d.z;
+        let b = 2.0 * (o.x * d.x - o.y * d.y + o.z * d.z);
+        let c = o.x * o.x - o.y * o.y + o.z * o.z;
+        // tolerances relative to the direction, which shrinks as the cone
+        // is scaled up
+        if a.abs() >= f64::EPSILON * dot(d, d) {
+            for t in truncated_roots(r, a, b, c, self.minimum, self.maximum) {
+                xs.push(intersection(t, self));
+            }
+        } else if b != 0.0 {
+            // the ray is parallel to one of the cone's halves, so it
+            // only crosses the other half, once
+            let t = -c / (2.0 * b);
+            let y = o.y + t * d.y;
+            if self.minimum < y && y < self.maximum {
+                xs.push(intersection(t, self));
+            }
+        }
+        if self.closed && d.y != 0.0 {
+            for cap in [self.minimum, self.maximum].iter() {
+                let t = (cap - o.y) / d.y;
+                if check_cap(r, t, cap.abs()) {
+                    xs.push(intersection(t, self));
+                }
+            }
+        }
+        xs
+    }
+
+    fn local_normal_at(&self, p: Tuple, _hit: &Intersection) -> Tuple {
+        let dist = p.x * p.x + p.z * p.z;
+        if dist < self.maximum * self.maximum && p.y >= self.maximum - ERR {
+            Tuple::vector(0.0, 1.0, 0.0)
+        } else if dist < self.minimum * self.minimum && p.y <= self.minimum + ERR {
+            Tuple::vector(0.0, -1.0, 0.0)
+        } else {
+            let mut y = dist.sqrt();
+            if p.y > 0.0 {
+                y = -y;
+            }
+            Tuple::vector(p.x, y, p.z)
+        }
+    }
+
+    // the radius is widest at whichever end is furthest from the tip
+    fn bounds(&self) -> BoundingBox {
+        let limit = self.minimum.abs().max(self.maximum.abs());
+        bounding_box(
+            Tuple::point(-limit, self.minimum, -limit),
+            Tuple::point(limit, self.maximum, limit),
+        )
+    }
+}
+
+// a collection of shapes transformed together as one. the group owns its
+// children, and keeps their parent transforms up to date whenever it is
+// transformed or placed inside another group.
+#[derive(Debug)]
+pub struct Group {
+    transform: Matrix4<f64>,
+    parent_transform: Matrix4<f64>,
+    // groups have no surface, but every shape has a material
+    material: Material,
+    children: Vec<Box<dyn Shape>>,
+    // children can't be changed once added, so their combined bounds are
+    // kept as they arrive rather than recomputed for every ray
+    bounds: BoundingBox,
+}
+
+pub fn group() -> Group {
+    Group {
+        transform: Matrix4::identity(),
+        parent_transform: Matrix4::identity(),
+        material: material(),
+        children: vec![],
+        bounds: empty_bounding_box(),
+    }
+}
+
+impl Group {
+    pub fn add_child(&mut self, mut child: Box<dyn Shape>) {
+        child.set_parent_transform(self.parent_transform * self.transform);
+        self.bounds.add_box(&child.parent_space_bounds());
+        self.children.push(child);
+    }
+
+    pub fn children(&self) -> &[Box<dyn Shape>] {
+        &self.children
+    }
+
+    pub fn set_transform(&mut self, m: Matrix4<f64>) {
+        self.transform = m;
+        self.update_children();
+    }
+
+    fn update_children(&mut self) {
+        let m = self.parent_transform * self.transform;
+        for child in self.children.iter_mut() {
+            child.set_parent_transform(m);
+        }
+    }
+
+    // removes and returns the children that fit entirely inside each half
+    // of the group's bounds. children straddling both halves stay put.
+    #[allow(clippy::type_complexity)]
+    fn partition_children(&mut self) -> (Vec<Box<dyn Shape>>, Vec<Box<dyn Shape>>) {
+        let (left_bounds, right_bounds) = self.bounds.split();
+        let mut left = vec![];
+        let mut right = vec![];
+        for child in std::mem::take(&mut self.children) {
+            let b = child.parent_space_bounds();
+            if left_bounds.contains_box(&b) {
+                left.push(child);
+            } else if right_bounds.contains_box(&b) {
+                right.push(child);
+            } else {
+                self.children.push(child);
+            }
+        }
+        (left, right)
+    }
+
+    // wraps the shapes in a new untransformed group and adds it as a child
+    fn make_subgroup(&mut self, shapes: Vec<Box<dyn Shape>>) {
+        let mut sub = group();
+        sub.set_parent_transform(self.parent_transform * self.transform);
+        for shape in shapes {
+            sub.add_child(shape);
+        }
+        self.add_child(Box::new(sub));
+    }
+}
+
+impl Shape for Group {
+    fn transform(&self) -> &Matrix4<f64> {
+        &self.transform
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn parent_transform(&self) -> &Matrix4<f64> {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, m: Matrix4<f64>) {
+        self.parent_transform = m;
+        self.update_children();
+    }
+
+    // each child moves the ray on into its own object space
+    fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>> {
+        // a ray that misses the bounds can't hit any child
+        if !self.bounds.intersects(r) {
+            return vec![];
+        }
+        let mut xs = vec![];
+        for child in self.children.iter() {
+            xs.extend_from_slice(&child.intersect(r));
+        }
+        xs
+    }
+
+    fn includes(&self, other: &dyn Shape) -> bool {
+        self.children.iter().any(|child| child.includes(other))
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.bounds
+    }
+
+    fn divide(&mut self, threshold: usize) {
+        let count = self.children.len();
+        if threshold <= count {
+            let (left, right) = self.partition_children();
+            if left.len() == count || right.len() == count {
+                // every child fits in one half, as when they all share a
+                // single point: a subgroup would only split the same way
+                self.children = if left.is_empty() { right } else { left };
+            } else {
+                if !left.is_empty() {
+                    self.make_subgroup(left);
+                }
+                if !right.is_empty() {
+                    self.make_subgroup(right);
+                }
+            }
+        }
+        for child in self.children.iter_mut() {
+            child.divide(threshold);
+        }
+    }
+
+    fn local_normal_at(&self, _p: Tuple, _hit: &Intersection) -> Tuple {
+        panic!("groups have no surface; normals come from the child that was hit")
+    }
+}
+
+// a triangle between three points. a smooth triangle also carries a
+// normal for each point, and interpolates between them across its face.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Triangle {
+    pub transform: Matrix4<f64>,
+    parent_transform: Matrix4<f64>,
+    pub material: Material,
+    p1: Tuple,
+    p2: Tuple,
+    p3: Tuple,
+    // precomputed edges, sharing p1
+    e1: Tuple,
+    e2: Tuple,
+    normal: Tuple,
+    // vertex normals for p1, p2 and p3
+    normals: Option<[Tuple; 3]>,
+}
+
+pub fn triangle(p1: Tuple, p2: Tuple, p3: Tuple) -> Triangle {
+    let e1 = p2 - p1;
+    let e2 = p3 - p1;
+    Triangle {
+        transform: Matrix4::identity(),
+        parent_transform: Matrix4::identity(),
+        material: material(),
+        p1,
+        p2,
+        p3,
+        e1,
+        e2,
+        normal: normalize(cross(e2, e1)),
+        normals: None,
+    }
+}
+
+pub fn smooth_triangle(
+    p1: Tuple,
+    p2: Tuple,
+    p3: Tuple,
+    n1: Tuple,
+    n2: Tuple,
+    n3: Tuple,
+) -> Triangle {
+    let mut t = triangle(p1, p2, p3);
+    t.normals = Some([n1, n2, n3]);
+    t
+}
+
+impl Triangle {
+    pub fn vertices(&self) -> [Tuple; 3] {
+        [self.p1, self.p2, self.p3]
+    }
+}
+
+impl Shape for Triangle {
+    fn transform(&self) -> &Matrix4<f64> {
+        &self.transform
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn parent_transform(&self) -> &Matrix4<f64> {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, m: Matrix4<f64>) {
+        self.parent_transform = m;
+    }
+
+    // moller-trumbore: solves for t along the ray and the barycentric
+    // u and v of the hit at the same time
+    fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>> {
+        let dir_cross_e2 = cross(r.direction, self.e2);
+        let det = dot(self.e1, dir_cross_e2);
+        // the ray is parallel to the triangle. det scales with the
+        // triangle's size, so a tolerance like ERR would miss the small
+        // faces of a detailed mesh.
+        if det.abs() < f64::EPSILON {
+            return vec![];
+        }
+        let f = 1.0 / det;
+        let p1_to_origin = r.origin - self.p1;
+        let u = f * dot(p1_to_origin, dir_cross_e2);
+        if !(0.0..=1.0).contains(&u) {
+            return vec![];
+        }
+        let origin_cross_e1 = cross(p1_to_origin, self.e1);
+        let v = f * dot(r.direction, origin_cross_e1);
+        if v < 0.0 || u + v > 1.0 {
+            return vec![];
+        }
+        let t = f * dot(self.e2, origin_cross_e1);
+        vec![intersection_with_uv(t, self, u, v)]
+    }
+
+    fn local_normal_at(&self, _p: Tuple, hit: &Intersection) -> Tuple {
+        match self.normals {
+            Some([n1, n2, n3]) => n2 * hit.u + n3 * hit.v + n1 * (1.0 - hit.u - hit.v),
+            None => self.normal,
+        }
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let mut b = empty_bounding_box();
+        for p in self.vertices().iter() {
+            b.add_point(*p);
+        }
+        b
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CsgOperation {
+    // everything inside either shape
+    Union,
+    // only what is inside both shapes
+    Intersection,
+    // what is inside the left shape but not the right
+    Difference,
+}
+
+// constructive solid geometry: two shapes combined into one by an operation.
+// like a group, it owns its children and keeps their parent transforms
+// up to date.
+#[derive(Debug)]
+pub struct Csg {
+    transform: Matrix4<f64>,
+    parent_transform: Matrix4<f64>,
+    material: Material,
+    pub operation: CsgOperation,
+    left: Box<dyn Shape>,
+    right: Box<dyn Shape>,
+}
+
+pub fn csg(operation: CsgOperation, left: Box<dyn Shape>, right: Box<dyn Shape>) -> Csg {
+    let mut c = Csg {
+        transform: Matrix4::identity(),
+        parent_transform: Matrix4::identity(),
+        material: material(),
+        operation,
+        left,
+        right,
+    };
+    c.update_children();
+    c
+}
+
+// whether a hit on one side is part of the combined surface, given which
+// side was hit and whether the ray is currently inside each side
+fn intersection_allowed(op: CsgOperation, lhit: bool, inl: bool, inr: bool) -> bool {
+    match op {
+        CsgOperation::Union => (lhit && !inr) || (!lhit && !inl),
+        CsgOperation::Intersection => (lhit && inr) || (!lhit && inl),
+        CsgOperation::Difference => (lhit && !inr) || (!lhit && inl),
+    }
+}
+
+impl Csg {
+    pub fn left(&self) -> &dyn Shape {
+        self.left.as_ref()
+    }
+
+    pub fn right(&self) -> &dyn Shape {
+        self.right.as_ref()
+    }
+
+    pub fn set_transform(&mut self, m: Matrix4<f64>) {
+        self.transform = m;
+        self.update_children();
+    }
+
+    fn update_children(&mut self) {
+        let m = self.parent_transform * self.transform;
+        self.left.set_parent_transform(m);
+        self.right.set_parent_transform(m);
+    }
+
+    // keeps only the intersections that lie on the combined surface.
+    // xs must be sorted, as the walk tracks entering and leaving each side.
+    pub fn filter_intersections<'a>(&self, xs: &[Intersection<'a>]) -> Vec<Intersection<'a>> {
+        let mut inl = false;
+        let mut inr = false;
+        let mut result = vec![];
+        for i in xs.iter() {
+            let lhit = self.left.includes(i.object);
+            if intersection_allowed(self.operation, lhit, inl, inr) {
+                result.push(*i);
+            }
+            if lhit {
+                inl = !inl;
+            } else {
+                inr = !inr;
+            }
+        }
+        result
+    }
+}
+
+impl Shape for Csg {
+    fn transform(&self) -> &Matrix4<f64> {
+        &self.transform
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn parent_transform(&self) -> &Matrix4<f64> {
+        &self.parent_transform
+    }
+
+    fn set_parent_transform(&mut self, m: Matrix4<f64>) {
+        self.parent_transform = m;
+        self.update_children();
+    }
+
+    fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>> {
+        let mut xs = self.left.intersect(r).to_vec();
+        xs.extend_from_slice(&self.right.intersect(r));
+        self.filter_intersections(&intersections(xs))
+    }
+
+    fn local_normal_at(&self, _p: Tuple, _hit: &Intersection) -> Tuple {
+        panic!("csg shapes have no surface; normals come from the child that was hit")
+    }
+
+    fn includes(&self, other: &dyn Shape) -> bool {
+        self.left.includes(other) || self.right.includes(other)
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let mut b = self.left.parent_space_bounds();
+        b.add_box(&self.right.parent_space_bounds());
+        b
+    }
+
+    fn divide(&mut self, threshold: usize) {
+        self.left.divide(threshold);
+        self.right.divide(threshold);
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Intersection<'a> {
+    pub t: f64,
+    pub object: &'a dyn Shape,
+    // where on the surface the hit landed, for shapes that care
+    // (the barycentric coordinates of a triangle hit)
+    pub u: f64,
+    pub v: f64,
+}
+
+pub fn intersection(t: f64, object: &dyn Shape) -> Intersection<'_> {
+    intersection_with_uv(t, object, 0.0, 0.0)
+}
+
+pub fn intersection_with_uv(t: f64, object: &dyn Shape, u: f64, v: f64) -> Intersection<'_> {
+    Intersection { t, object, u, v }
+}
+
+// intersections are always kept sorted by t
+#[derive(Debug, Clone, PartialEq)]
+pub struct Intersections<'a> {
+    list: Vec<Intersection<'a>>,
+}
+
+pub fn intersections(mut list: Vec<Intersection<'_>>) -> Intersections<'_> {
+    list.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(Ordering::Equal));
+    Intersections { list }
+}
+
+impl<'a> Intersections<'a> {
+    // the visible intersection is the lowest non-negative t;
+    // anything negative is behind the ray's origin
+    pub fn hit(&self) -> Option<&Intersection<'a>> {
+        self.list.iter().find(|i| i.t >= 0.0)
+    }
+}
+
+impl<'a> Deref for Intersections<'a> {
+    type Target = [Intersection<'a>];
+    fn deref(&self) -> &Self::Target {
+        &self.list
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Material {
+    pub color: Color,
+    // when set, the pattern is used in place of color
+    pub pattern: Option<Pattern>,
+    pub ambient: f64,
+    pub diffuse: f64,
+    pub specular: f64,
+    pub shininess: f64,
+    // 0 for a matte surface, 1 for a perfect mirror
+    pub reflective: f64,
+    // 0 for an opaque surface, 1 for a perfectly clear one
+    pub transparency: f64,
+    // how much light bends entering the material: 1.0 for a vacuum,
+    // around 1.33 for water and 1.5 for glass
+    pub refractive_index: f64,
+}
+
+pub fn material() -> Material {
+    Material {
+        color: color(1.0, 1.0, 1.0),
+        pattern: None,
+        ambient: 0.1,
+        diffuse: 0.9,
+        specular: 0.9,
+        shininess: 200.0,
+        reflective: 0.0,
+        transparency: 0.0,
+        refractive_index: 1.0,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PatternKind {
+    // alternates between the colors every unit of x
+    Stripe(Color, Color),
+    // blends linearly from the first color to the second along x
+    Gradient(Color, Color),
+    // concentric rings in the xz plane
+    Ring(Color, Color),
+    // alternating cubes in all three dimensions
+    Checkers(Color, Color),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    // applied on top of the transform of the object being painted
+    pub transform: Matrix4<f64>,
+}
+
+fn pattern(kind: PatternKind) -> Pattern {
+    Pattern {
+        kind,
+        transform: Matrix4::identity(),
+    }
+}
+
+pub fn stripe_pattern(a: Color, b: Color) -> Pattern {
+    pattern(PatternKind::Stripe(a, b))
+}
+
+pub fn gradient_pattern(a: Color, b: Color) -> Pattern {
+    pattern(PatternKind::Gradient(a, b))
+}
+
+pub fn ring_pattern(a: Color, b: Color) -> Pattern {
+    pattern(PatternKind::Ring(a, b))
+}
+
+pub fn checkers_pattern(a: Color, b: Color) -> Pattern {
+    pattern(PatternKind::Checkers(a, b))
+}
+
+fn is_even(x: f64) -> bool {
+    (x.floor() as i64).rem_euclid(2) == 0
+}
+
+impl Pattern {
+    // the color at a point in pattern space
+    pub fn pattern_at(&self, p: Tuple) -> Color {
+        match self.kind {
+            PatternKind::Stripe(a, b) => {
+                if is_even(p.x) {
+                    a
+                } else {
+                    b
+                }
+            }
+            PatternKind::Gradient(a, b) => a + (b - a) * (p.x - p.x.floor()),
+            PatternKind::Ring(a, b) => {
+                if is_even((p.x * p.x + p.z * p.z).sqrt()) {
+                    a
+                } else {
+                    b
+                }
+            }
+            PatternKind::Checkers(a, b) => {
+                if is_even(p.x.floor() + p.y.floor() + p.z.floor()) {
+                    a
+                } else {
+                    b
+                }
+            }
+        }
+    }
+
+    // the color at a point in world space on the given object
+    pub fn pattern_at_shape(&self, object: &dyn Shape, world_point: Tuple) -> Color {
+        let pattern_inv = self
+            .transform
+            .try_inverse()
+            .expect("a pattern with a singular transform can't be sampled");
+        self.pattern_at(pattern_inv * object.world_to_object(world_point))
+    }
+}
+
+// a light with no size, radiating equally in every direction
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PointLight {
+    pub position: Tuple,
+    pub intensity: Color,
+}
+
+pub fn point_light(position: Tuple, intensity: Color) -> PointLight {
+    PointLight {
+        position,
+        intensity,
+    }
+}
+
+// phong reflection: the sum of ambient, diffuse and specular contributions
+pub fn lighting(
+    material: &Material,
+    object: &dyn Shape,
+    light: &PointLight,
+    point: Tuple,
+    eyev: Tuple,
+    normalv: Tuple,
+    in_shadow: bool,
+) -> Color {
+    let black = color(0.0, 0.0, 0.0);
+    let surface_color = match material.pattern {
+        Some(pattern) => pattern.pattern_at_shape(object, point),
+        None => material.color,
+    };
+    let effective_color = surface_color * light.intensity;
+    let lightv = normalize(light.position - point);
+    let ambient = effective_color * material.ambient;
+    // only ambient light reaches a point the light can't see
+    if in_shadow {
+        return ambient;
+    }
+    // a negative cosine means the light is on the other side of the surface
+    let light_dot_normal = dot(lightv, normalv);
+    if light_dot_normal < 0.0 {
+        return ambient;
+    }
+    let diffuse = effective_color * material.diffuse * light_dot_normal;
+    // a negative cosine means the light reflects away from the eye
+    let reflectv = reflect(-lightv, normalv);
+    let reflect_dot_eye = dot(reflectv, eyev);
+    let specular = if reflect_dot_eye <= 0.0 {
+        black
+    } else {
+        let factor = reflect_dot_eye.powf(material.shininess);
+        light.intensity * material.specular * factor
+    };
+    ambient + diffuse + specular
+}
+
+// the most children a group keeps before it is divided into a bounding
+// volume hierarchy
+pub const BVH_THRESHOLD: usize = 8;
+
+// objects added with add_object are divided into a bounding volume
+// hierarchy on the way in. objects pushed straight onto `objects` are left
+// as they are until divide is called.
+pub struct World {
+    pub objects: Vec<Box<dyn Shape>>,
+    pub lights: Vec<PointLight>,
+}
+
+impl World {
+    pub fn add_object(&mut self, mut object: Box<dyn Shape>) {
+        object.divide(BVH_THRESHOLD);
+        self.objects.push(object);
+    }
+
+    pub fn divide(&mut self, threshold: usize) {
+        for object in self.objects.iter_mut() {
+            object.divide(threshold);
+        }
+    }
+}
+
+pub fn world() -> World {
+    World {
+        objects: vec![],
+        lights: vec![],
+    }
+}
+
+// two concentric spheres lit from the upper left, as used throughout the book
+pub fn default_world() -> World {
+    let light = point_light(Tuple::point(-10.0, 10.0, -10.0), color(1.0, 1.0, 1.0));
+    let mut s1 = sphere();
+    s1.material.color = color(0.8, 1.0, 0.6);
+    s1.material.diffuse = 0.7;
+    s1.material.specular = 0.2;
+    let mut s2 = sphere();
+    s2.transform = scaling(0.5, 0.5, 0.5);
+    World {
+        objects: vec![Box::new(s1), Box::new(s2)],
+        lights: vec![light],
+    }
+}
+
+pub fn intersect_world<'a>(w: &'a World, r: &Ray) -> Intersections<'a> {
+    let mut list = vec![];
+    for object in w.objects.iter() {
+        list.extend_from_slice(&object.intersect(r));
+    }
+    intersections(list)
+}
+
+// everything about an intersection that shading needs, computed once
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Computations<'a> {
+    pub t: f64,
+    pub object: &'a dyn Shape,
+    pub point: Tuple,
+    pub eyev: Tuple,
+    pub normalv: Tuple,
+    pub reflectv: Tuple,
+    pub inside: bool,
+    // the point nudged off the surface along the normal, so rays cast
+    // from it don't immediately re-intersect the surface they left
+    pub over_point: Tuple,
+    // the point nudged just below the surface, where refracted rays start
+    pub under_point: Tuple,
+    // refractive indices of the materials being exited and entered
+    pub n1: f64,
+    pub n2: f64,
+}
+
+// `xs` is every intersection along the ray, including `i`; it is needed to
+// work out which objects the ray is inside of when it reaches `i`
+pub fn prepare_computations<'a>(
+    i: &Intersection<'a>,
+    r: &Ray,
+    xs: &[Intersection<'a>],
+) -> Computations<'a> {
+    let (n1, n2) = refractive_indices(i, xs);
+    let point = r.position(i.t);
+    let eyev = -r.direction;
+    let mut normalv = i.object.normal_at(point, i);
+    // the normal points away from the eye, so the hit is inside the object
+    let inside = dot(normalv, eyev) < 0.0;
+    if inside {
+        normalv = -normalv;
+    }
+    Computations {
+        t: i.t,
+        object: i.object,
+        point,
+        eyev,
+        normalv,
+        reflectv: reflect(r.direction, normalv),
+        inside,
+        over_point: point + normalv * ERR,
+        under_point: point - normalv * ERR,
+        n1,
+        n2,
+    }
+}
+
+// walks the intersections up to the hit, tracking which objects the ray
+// is currently inside. n1 belongs to the innermost object before the hit,
+// n2 to the innermost object after it; empty space counts as a vacuum.
+fn refractive_indices(hit: &Intersection, xs: &[Intersection]) -> (f64, f64) {
+    let mut containers: Vec<&dyn Shape> = vec![];
+    let mut n1 = 1.0;
+    let mut n2 = 1.0;
+    for i in xs.iter() {
+        if i == hit {
+            n1 = containers
+                .last()
+                .map_or(1.0, |o| o.material().refractive_index);
+        }
+        match containers.iter().position(|o| *o == i.object) {
+            Some(index) => {
+                containers.remove(index);
+            }
+            None => containers.push(i.object),
+        }
+        if i == hit {
+            n2 = containers
+                .last()
+                .map_or(1.0, |o| o.material().refractive_index);
+            break;
+        }
+    }
+    (n1, n2)
+}
+
+// `remaining` is how many more bounces a reflected ray may take
+pub fn shade_hit(w: &World, comps: &Computations, remaining: usize) -> Color {
+    let mut c = color(0.0, 0.0, 0.0);
+    for light in w.lights.iter() {
+        // shading from over_point avoids the surface shadowing itself
+        let in_shadow = is_shadowed(w, light, comps.over_point);
+        c = c + lighting(
+            comps.object.material(),
+            comps.object,
+            light,
+            comps.over_point,
+            comps.eyev,
+            comps.normalv,
+            in_shadow,
+        );
+    }
+    let reflected = reflected_color(w, comps, remaining);
+    let refracted = refracted_color(w, comps, remaining);
+    let m = comps.object.material();
+    if m.reflective > 0.0 && m.transparency > 0.0 {
+        // glass reflects more of the scene the more obliquely it is seen
+        let reflectance = schlick(comps);
+        c + reflected * reflectance + refracted * (1.0 - reflectance)
+    } else {
+        c + reflected + refracted
+    }
+}
+
+// the color seen in the surface's reflection, black for a matte surface
+// or once the ray has run out of bounces
+pub fn reflected_color(w: &World, comps: &Computations, remaining: usize) -> Color {
+    let reflective = comps.object.material().reflective;
+    if remaining == 0 || reflective == 0.0 {
+        return color(0.0, 0.0, 0.0);
+    }
+    let reflect_ray = ray(comps.over_point, comps.reflectv);
+    color_at(w, &reflect_ray, remaining - 1) * reflective
+}
+
+// the color seen through the surface, black for an opaque surface, once
+// the ray has run out of bounces, or under total internal reflection
+pub fn refracted_color(w: &World, comps: &Computations, remaining: usize) -> Color {
+    let transparency = comps.object.material().transparency;
+    if remaining == 0 || transparency == 0.0 {
+        return color(0.0, 0.0, 0.0);
+    }
+    // snell's law
+    let n_ratio = comps.n1 / comps.n2;
+    let cos_i = dot(comps.eyev, comps.normalv);
+    let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        // total internal reflection: no light gets through
+        return color(0.0, 0.0, 0.0);
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
+    let refract_ray = ray(comps.under_point, direction);
+    color_at(w, &refract_ray, remaining - 1) * transparency
+}
+
+// schlick's approximation of the fresnel effect: the fraction of light
+// that is reflected rather than refracted at the hit
+pub fn schlick(comps: &Computations) -> f64 {
+    let mut cos = dot(comps.eyev, comps.normalv);
+    if comps.n1 > comps.n2 {
+        let n = comps.n1 / comps.n2;
+        let sin2_t = n * n * (1.0 - cos * cos);
+        if sin2_t > 1.0 {
+            return 1.0;
+        }
+        // use cos(theta_t) when leaving the denser material
+        cos = (1.0 - sin2_t).sqrt();
+    }
+    let r0 = ((comps.n1 - comps.n2) / (comps.n1 + comps.n2)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
+// whether anything lies between the point and the light
+pub fn is_shadowed(w: &World, light: &PointLight, point: Tuple) -> bool {
+    let v = light.position - point;
+    let distance = magnitude(v);
+    let r = ray(point, normalize(v));
+    match intersect_world(w, &r).hit() {
+        Some(hit) => hit.t < distance,
+        None => false,
+    }
+}
+
+// the color seen along the ray, black if it hits nothing
+pub fn color_at(w: &World, r: &Ray, remaining: usize) -> Color {
+    let xs = intersect_world(w, r);
+    match xs.hit() {
+        Some(hit) => shade_hit(w, &prepare_computations(hit, r, &xs), remaining),
+        None => color(0.0, 0.0, 0.0),
+    }
+}
+
+// how many times a ray may bounce between reflective surfaces
+// unless a camera is told otherwise
+pub const DEFAULT_MAX_DEPTH: usize = 5;
+
+// orients the world relative to an eye at `from` looking towards `to`
+pub fn view_transform(from: Tuple, to: Tuple, up: Tuple) -> Matrix4<f64> {
+    let forward = normalize(to - from);
+    let left = cross(forward, normalize(up));
+    // recompute up so that it is exactly perpendicular to forward and left
+    let true_up = cross(left, forward);
+    #[rustfmt::skip]
+    let orientation = Matrix4::from_row_slice(&[
+        left.x,     left.y,     left.z,     0.0,
+        true_up.x,  true_up.y,  true_up.z,  0.0,
+        -forward.x, -forward.y, -forward.z, 0.0,
+        0.0,        0.0,        0.0,        1.0,
+    ]);
+    orientation * translation(-from.x, -from.y, -from.z)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Camera {
+    pub hsize: usize,
+    pub vsize: usize,
+    pub field_of_view: f64,
+    pub transform: Matrix4<f64>,
+    // the recursion limit for reflected rays, so that two facing
+    // mirrors can't bounce a ray back and forth forever
+    pub max_depth: usize,
+    half_width: f64,
+    half_height: f64,
+    pixel_size: f64,
+}
+
+// the canvas sits one unit in front of the camera
+pub fn camera(hsize: usize, vsize: usize, field_of_view: f64) -> Camera {
+    let half_view = (field_of_view / 2.0).tan();
+    let aspect = hsize as f64 / vsize as f64;
+    let (half_width, half_height) = if aspect >= 1.0 {
+        (half_view, half_view / aspect)
+    } else {
+        (half_view * aspect, half_view)
+    };
+    Camera {
+        hsize,
+        vsize,
+        field_of_view,
+        transform: Matrix4::identity(),
+        max_depth: DEFAULT_MAX_DEPTH,
+        half_width,
+        half_height,
+        pixel_size: (half_width * 2.0) / hsize as f64,
+    }
+}
+
+impl Camera {
+    pub fn pixel_size(&self) -> f64 {
+        self.pixel_size
+    }
+
+    // a ray from the camera through the centre of the given pixel
+    pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
+        let xoffset = (px as f64 + 0.5) * self.pixel_size;
+        let yoffset = (py as f64 + 0.5) * self.pixel_size;
+        // the camera looks down -z, so +x is to the left
+        let world_x = self.half_width - xoffset;
+        let world_y = self.half_height - yoffset;
+        let inv = self
+            .transform
+            .try_inverse()
+            .expect("a camera with a singular transform can't see anything");
+        let pixel = inv * Tuple::point(world_x, world_y, -1.0);
+        let origin = inv * Tuple::point(0.0, 0.0, 0.0);
+        let direction = normalize(pixel - origin);
+        ray(origin, direction)
+    }
+
+    pub fn render(&self, w: &World) -> Canvas {
+        let mut image = canvas(self.hsize, self.vsize);
+        for y in 0..self.vsize {
+            for x in 0..self.hsize {
+                let r = self.ray_for_pixel(x, y);
+                write_pixel(&mut image, x, y, color_at(w, &r, self.max_depth));
+            }
+        }
+        image
+    }
+}
+
+// the triangles and vertex data read from a wavefront obj file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjFile {
+    pub vertices: Vec<Tuple>,
+    pub normals: Vec<Tuple>,
+    // faces that appear before any named group
+    pub default_group: Vec<Triangle>,
+    // faces under each `g` statement, in the order the names first appear
+    pub groups: Vec<(String, Vec<Triangle>)>,
+    // how many lines were not understood and skipped
+    pub ignored: usize,
+}
+
+// parses the v, vn, f and g statements of an obj file. anything else,
+// including statements that refer to missing vertices, is skipped and
+// counted in `ignored`.
+pub fn parse_obj_file(input: &str) -> ObjFile {
+    let mut obj = ObjFile {
+        vertices: vec![],
+        normals: vec![],
+        default_group: vec![],
+        groups: vec![],
+        ignored: 0,
+    };
+    // index into obj.groups, None for the default group
+    let mut current: Option<usize> = None;
+    for line in input.lines() {
+        let mut words = line.split_whitespace();
+        let parsed = match words.next() {
+            Some("v") => parse_obj_numbers(words).map(|n| {
+                obj.vertices.push(Tuple::point(n[0], n[1], n[2]));
+            }),
+            Some("vn") => parse_obj_numbers(words).map(|n| {
+                obj.normals.push(Tuple::vector(n[0], n[1], n[2]));
+            }),
+            Some("f") => parse_obj_face(&obj, words).map(|triangles| match current {
+                Some(index) => obj.groups[index].1.extend(triangles),
+                None => obj.default_group.extend(triangles),
+            }),
+            // an unnamed g goes back to the default group
+            Some("g") => {
+                current =
+                    words.next().map(
+                        |name| match obj.groups.iter().position(|(n, _)| n == name) {
+                            Some(index) => index,
+                            None => {
+                                obj.groups.push((name.to_string(), vec![]));
+                                obj.groups.len() - 1
+                            }
+                        },
+                    );
+                Some(())
+            }
+            // blank lines aren't worth reporting
+            None => Some(()),
+            Some(_) => None,
+        };
+        if parsed.is_none() {
+            obj.ignored += 1;
+        }
+    }
+    obj
+}
+
+// the first three numbers of a v or vn statement
+fn parse_obj_numbers<'a>(words: impl Iterator<Item = &'a str>) -> Option<[f64; 3]> {
+    let numbers: Vec<f64> = words.map(str::parse).collect::<Result<_, _>>().ok()?;
+    if numbers.len() < 3 {
+        return None;
+    }
+    Some([numbers[0], numbers[1], numbers[2]])
+}
+
+// resolves a 1-based obj index, where negative indices count back from
+// the most recently defined element
+fn obj_index<T: Copy>(list: &[T], word: &str) -> Option<T> {
+    let index: i64 = word.parse().ok()?;
+    let resolved = if index < 0 {
+        list.len() as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 {
+        return None;
+    }
+    list.get(resolved as usize).copied()
+}
+
+// a polygon of any number of v, v/vt, v//vn or v/vt/vn vertices,
+// triangulated as a fan around its first vertex
+fn parse_obj_face<'a>(
+    obj: &ObjFile,
+    words: impl Iterator<Item = &'a str>,
+) -> Option<Vec<Triangle>> {
+    let mut points = vec![];
+    let mut normals = vec![];
+    for word in words {
+        let mut parts = word.split('/');
+        points.push(obj_index(&obj.vertices, parts.next()?)?);
+        // texture coordinates are not supported, so skip them
+        parts.next();
+        if let Some(n) = parts.next() {
+            normals.push(obj_index(&obj.normals, n)?);
+        }
+    }
+    if points.len() < 3 {
+        return None;
+    }
+    // only smooth the face if every vertex has a normal
+    let smooth = normals.len() == points.len();
+    let mut triangles = vec![];
+    for i in 1..points.len() - 1 {
+        triangles.push(if smooth {
+            smooth_triangle(
+                points[0],
+                points[i],
+                points[i + 1],
+                normals[0],
+                normals[i],
+                normals[i + 1],
+            )
+        } else {
+            triangle(points[0], points[i], points[i + 1])
+        });
+    }
+    Some(triangles)
+}
+
+impl ObjFile {
+    pub fn group(&self, name: &str) -> Option<&[Triangle]> {
+        self.groups
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, triangles)| triangles.as_slice())
+    }
+
+    // a single group holding a child group for the default group and for
+    // each named group, ready to be transformed and placed in a world.
+    // large groups are divided into a bounding volume hierarchy.
+    pub fn obj_to_group(&self) -> Group {
+        let mut g = group();
+        let all = std::iter::once(&self.default_group).chain(self.groups.iter().map(|(_, t)| t));
+        for triangles in all.filter(|t| !t.is_empty()) {
+            let mut child = group();
+            for t in triangles.iter() {
+                child.add_child(Box::new(*t));
+            }
+            g.add_child(Box::new(child));
+        }
+        g.divide(BVH_THRESHOLD);
+        g
+    }
+}
+
+#[cfg(test)]
+#[macro_use]
+extern crate approx;
+mod tests {
+    use super::*;
+    use na::{Dynamic, Matrix, Point3, VecStorage, Vector3};
+
+    // initial tests
+    #[test]
+    fn test_page30() {
+        assert_eq!(
+            Tuple {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+                w: 4.0
+            },
+            Tuple {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+                w: 4.0
+            }
+        );
+
+        let p = Tuple::new(4.30, -4.2, 3.1, 1.0);
+        assert_eq!(p.x, 4.3);
+        assert_eq!(p.y, -4.2);
+        assert_eq!(p.z, 3.1);
+        assert_eq!(p.w, 1.0);
+        assert_eq!(p.is_point(), true);
+        assert_eq!(p.is_vector(), false);
+
+        let p = Tuple::new(4.30, -4.2, 3.1, 0.0);
+        assert_eq!(p.x, 4.3);
+        assert_eq!(p.y, -4.2);
+        assert_eq!(p.z, 3.1);
+        assert_eq!(p.w, 0.0);
+        assert_eq!(p.is_point(), false);
+        assert_eq!(p.is_vector(), true);
+    }
+
+    #[test]
+    fn test_point() {
+        let p = Tuple::point(4.0, -4.0, 3.0);
+        assert_eq!(p, Tuple::new(4.0, -4.0, 3.0, 1.0))
+    }
+
+    #[test]
+    fn test_comparison() {
+        let a = Tuple::new(1.0, 2.0, 3.0, 4.0);
+        let b = Tuple::new(1.0, 2.0, 3.0, 4.0 + (ERR / 2.0));
+        assert!(Tuple::eq(a, b.clone()));
+        let c = Tuple::new(4.0, 3.0, 2.0, 1.0);
+        assert!(Tuple::eq(b, c));
+    }
+
+    // adding two tuples
+    #[test]
+    fn test_add() {
+        let a = Tuple::new(3.0, -2.0, 5.0, 1.0);
+        let b = Tuple::new(-2.0, 3.0, 1.0, 0.0);
+        assert_eq!(a + b, Tuple::new(1.0, 1.0, 6.0, 1.0));
+    }
+
+    // subtracing two points
+    #[test]
+    fn test_sub() {
+        let p1 = Tuple::point(3.0, 2.0, 1.0);
+        let p2 = Tuple::point(5.0, 6.0, 7.0);
+        assert_eq!(p1 - p2, Tuple::vector(-2.0, -4.0, -6.0));
     }
 
     // subtracting a vector from a point
     #[test]
-    fn test_sub_vec() {
-        let p = Tuple::point(3.0, 2.0, 1.0);
-        let v = Tuple::vector(5.0, 6.0, 7.0);
-        assert_eq!(p - v, Tuple::point(-2.0, -4.0, -6.0))
+    fn test_sub_vec() {
+        let p = Tuple::point(3.0, 2.0, 1.0);
+        let v = Tuple::vector(5.0, 6.0, 7.0);
+        assert_eq!(p - v, Tuple::point(-2.0, -4.0, -6.0))
+    }
+
+    // subtracting two vectors
+    #[test]
+    fn test_sub_vecs() {
+        let v1 = Tuple::vector(3.0, 2.0, 1.0);
+        let v2 = Tuple::vector(5.0, 6.0, 7.0);
+        assert_eq!(v1 - v2, Tuple::vector(-2.0, -4.0, -6.0));
+    }
+
+    // subtracting a vector from the zero vector
+    #[test]
+    fn test_sub_a_vec_from_zero() {
+        let z = Tuple::ZERO.clone();
+        let v = Tuple::vector(1.0, -2.0, -3.0);
+        assert_eq!(z - v, Tuple::vector(-1.0, 2.0, 3.0));
+    }
+
+    // negating a tuple
+    #[test]
+    fn test_neg() {
+        let a = Tuple::new(1.0, -2.0, 3.0, -4.0);
+        assert_eq!(-a, Tuple::new(-1.0, 2.0, -3.0, 4.0));
+    }
+
+    // multiplying a tuple by a scalar
+    #[test]
+    fn test_scalar_mult() {
+        let a = Tuple::new(1.0, -2.0, 3.0, -4.0);
+        assert_eq!(a * 3.5, Tuple::new(3.5, -7.0, 10.5, -14.0))
+    }
+
+    #[test]
+    fn test_fraction_mult() {
+        let a = Tuple::new(1.0, -2.0, 3.0, -4.0);
+        assert_eq!(a * 0.5, Tuple::new(0.5, -1.0, 1.5, -2.0))
+    }
+
+    #[test]
+    fn test_dividing_a_tuple_by_a_scalar() {
+        let a = Tuple::new(1.0, -2.0, 3.0, -4.0);
+        assert_eq!(a / 2.0, Tuple::new(0.5, -1.0, 1.5, -2.0))
+    }
+
+    #[test]
+    fn test_magnitude() {
+        let v = Tuple::vector(1.0, 0.0, 0.0);
+        assert_eq!(magnitude(v), 1.0)
+    }
+
+    #[test]
+    fn test_magnitude2() {
+        let v = Tuple::vector(0.0, 1.0, 0.0);
+        assert_eq!(magnitude(v), 1.0)
+    }
+
+    #[test]
+    fn test_magnitude3() {
+        let v = Tuple::vector(0.0, 0.0, 1.0);
+        assert_eq!(magnitude(v), 1.0)
+    }
+    #[test]
+    fn test_magnitude4() {
+        let v = Tuple::vector(1.0, 2.0, 3.0);
+        assert_eq!(magnitude(v), 14.0_f64.sqrt())
+    }
+    #[test]
+    fn test_magnitude5() {
+        let v = Tuple::vector(-1.0, -2.0, -3.0);
+        assert_eq!(magnitude(v), 14.0_f64.sqrt())
+    }
+    #[test]
+    fn test_normalizing1() {
+        let v = Tuple::vector(4.0, 0.0, 0.0);
+        assert_eq!(normalize(v), Tuple::vector(1.0, 0.0, 0.0))
+    }
+    #[test]
+    fn test_normalizing2() {
+        let v = Tuple::vector(1.0, 2.0, 3.0);
+        assert!(Tuple::eq(
+            normalize(v),
+            Tuple::vector(0.26726, 0.53452, 0.80178)
+        ))
+    }
+    #[test]
+    fn test_normalizing3() {
+        let v = Tuple::vector(1.0, 2.0, 3.0);
+        let norm = normalize(v);
+        assert_eq!(magnitude(norm), 1.0)
+    }
+    #[test]
+    fn test_dot_product() {
+        let a = Tuple::vector(1.0, 2.0, 3.0);
+        let b = Tuple::vector(2.0, 3.0, 4.0);
+        assert_eq!(dot(a, b), 20.0)
+    }
+    #[test]
+    fn test_cross_product() {
+        let a = Tuple::vector(1.0, 2.0, 3.0);
+        let b = Tuple::vector(2.0, 3.0, 4.0);
+        assert_eq!(cross(a, b), Tuple::vector(-1.0, 2.0, -1.0));
+        assert_eq!(cross(b, a), Tuple::vector(1.0, -2.0, 1.0));
+    }
+    #[test]
+    fn test_colors() {
+        let c = color(-0.5, 0.4, 1.7);
+        assert_eq!(c.red, -0.5);
+        assert_eq!(c.green, 0.4);
+        assert_eq!(c.blue, 1.7);
+    }
+    #[test]
+    fn test_colors_add() {
+        let c1 = color(0.9, 0.6, 0.75);
+        let c2 = color(0.7, 0.1, 0.25);
+        assert_eq!(c1 + c2, color(1.6, 0.7, 1.0))
+    }
+    #[test]
+    fn test_mult_scalar() {
+        let c = color(0.2, 0.3, 0.4);
+        assert_eq!(c * 2.0, color(0.4, 0.6, 0.8))
+    }
+    #[test]
+    fn test_mult_colors() {
+        let c1 = color(1.0, 0.2, 0.4);
+        let c2 = color(0.9, 1.0, 0.1);
+        assert_eq!(c1 * c2, color(0.9, 0.2, 0.04))
+    }
+    #[test]
+    fn test_canvas() {
+        let c = canvas(10, 20);
+        assert_eq!(c.width(), 10);
+        assert_eq!(c.height(), 20);
+        assert_eq!(c.pixels().len(), 200);
+        for p in c.pixels().iter() {
+            assert_eq!(*p, color(0.0, 0.0, 0.0));
+        }
+    }
+    #[test]
+    fn test_writing_pixels() {
+        let mut c = canvas(10, 20);
+        let red = color(1.0, 0.0, 0.0);
+        write_pixel(&mut c, 2, 3, red);
+        assert_eq!(pixel_at(&c, 2, 3), red);
+    }
+
+    #[test]
+    fn test_canvas_pixels_are_stored_a_row_at_a_time() {
+        let mut c = canvas(3, 2);
+        let red = color(1.0, 0.0, 0.0);
+        write_pixel(&mut c, 1, 0, red);
+        write_pixel(&mut c, 0, 1, red);
+        assert_eq!(c.pixels()[1], red);
+        assert_eq!(c.pixels()[3], red);
+        let rows: Vec<&[Color]> = c.rows().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1][0], red);
+    }
+
+    #[test]
+    fn test_checked_pixel_access() {
+        let mut c = canvas(10, 20);
+        let red = color(1.0, 0.0, 0.0);
+        assert_eq!(c.set(9, 19, red), Ok(()));
+        assert_eq!(c.get(9, 19), Some(red));
+        assert_eq!(c.set(10, 0, red), Err(OutOfBounds { x: 10, y: 0 }));
+        assert_eq!(c.set(0, 20, red), Err(OutOfBounds { x: 0, y: 20 }));
+        assert_eq!(c.get(10, 0), None);
+        assert_eq!(c.get(0, 20), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "canvas dimensions overflow")]
+    fn test_a_canvas_too_large_to_address_panics() {
+        canvas(1 << 32, 1 << 32);
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel (10, 3) is outside the canvas")]
+    fn test_writing_a_pixel_outside_the_canvas_panics() {
+        let mut c = canvas(10, 20);
+        write_pixel(&mut c, 10, 3, color(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_costructing_the_ppm_header() {
+        let c = canvas(5, 3);
+        let ppm = canvas_to_ppm(c);
+        let want = r#"P3
+5 3
+255
+"#;
+        let mut first_four = String::new();
+        for line in ppm.lines().take(3) {
+            first_four.push_str(line);
+            first_four.push('\n');
+        }
+
+        assert_eq!(first_four, want)
+    }
+
+    #[test]
+    fn test_ppm_pixel_data() {
+        let mut c = canvas(5, 3);
+        let c1 = color(1.5, 0.0, 0.0);
+        let c2 = color(0.0, 0.5, 0.0);
+        let c3 = color(-0.5, 0.0, 1.0);
+        write_pixel(&mut c, 0, 0, c1);
+        write_pixel(&mut c, 2, 1, c2);
+        write_pixel(&mut c, 4, 2, c3);
+        let ppm = canvas_to_ppm(c);
+        let mut four_to_six = String::new();
+        let want = r#"255 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+0 0 0 0 0 0 0 128 0 0 0 0 0 0 0
+0 0 0 0 0 0 0 0 0 0 0 0 0 0 255
+"#;
+        for line in ppm.lines().skip(3).take(4) {
+            four_to_six.push_str(line);
+            four_to_six.push('\n');
+        }
+        assert_eq!(four_to_six, want)
+    }
+
+    #[test]
+    fn test_splitting_long_lines_in_ppm() {
+        let mut c = canvas(10, 2);
+        let col = color(1.0, 0.8, 0.6);
+        for x in 0..c.width() {
+            for y in 0..c.height() {
+                write_pixel(&mut c, x, y, col);
+            }
+        }
+        let want = r#"255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204
+153 255 204 153 255 204 153 255 204 153 255 204 153
+255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204
+153 255 204 153 255 204 153 255 204 153 255 204 153
+"#;
+        let ppm = canvas_to_ppm(c);
+        let mut four_to_seven = String::new();
+        for line in ppm.lines().skip(3).take(4) {
+            four_to_seven.push_str(line);
+            four_to_seven.push('\n');
+        }
+        assert_eq!(four_to_seven, want)
+    }
+
+    #[test]
+    fn test_constructing_matrix() {
+        let m = DMatrix::from_row_slice(
+            4,
+            4,
+            &[
+                1.0, 2.0, 3.0, 4.0, 5.5, 6.5, 7.5, 8.5, 9.0, 10.0, 11.0, 12.0, 13.5, 14.5, 15.5,
+                16.5,
+            ],
+        );
+        assert_eq!(m[(0, 0)], 1.0);
+        assert_eq!(m[(0, 3)], 4.0);
+        assert_eq!(m[(1, 0)], 5.5);
+        assert_eq!(m[(1, 2)], 7.5);
+        assert_eq!(m[(2, 2)], 11.0);
+        assert_eq!(m[(3, 0)], 13.5);
+        assert_eq!(m[(3, 2)], 15.5);
+    }
+
+    #[test]
+    fn test_constructing_2dmatrix() {
+        let m = DMatrix::from_row_slice(2, 2, &[-3, 5, 1, -2]);
+        assert_eq!(m[(0, 0)], -3);
+        assert_eq!(m[(0, 1)], 5);
+        assert_eq!(m[(1, 0)], 1);
+        assert_eq!(m[(1, 1)], -2);
+    }
+
+    #[test]
+    fn test_multiplying_matrices() {
+        let a = DMatrix::from_row_slice(4, 4, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 8, 7, 6, 5, 4, 3, 2]);
+        let b = DMatrix::from_row_slice(4, 4, &[-2, 1, 2, 3, 3, 2, 1, -1, 4, 3, 6, 5, 1, 2, 7, 8]);
+        assert_eq!(
+            a * b,
+            DMatrix::from_row_slice(
+                4,
+                4,
+                &[20, 22, 50, 48, 44, 54, 114, 108, 40, 58, 110, 102, 16, 26, 46, 42]
+            )
+        );
+    }
+
+    #[test]
+    fn test_multiplication_by_tuple() {
+        let a = DMatrix::from_row_slice(4, 4, &[1, 2, 3, 4, 2, 4, 4, 2, 8, 6, 4, 1, 0, 0, 0, 1]);
+        let b = DMatrix::from_row_slice(4, 1, &[1, 2, 3, 1]);
+        assert_eq!(a * b, DMatrix::from_row_slice(4, 1, &[18, 24, 33, 1]));
+    }
+
+    #[test]
+    fn test_multiplicative_identity() {
+        let a = DMatrix::from_row_slice(4, 4, &[0, 1, 2, 4, 1, 2, 4, 8, 2, 4, 8, 16, 4, 8, 16, 32]);
+        let id = DMatrix::identity(4, 4);
+        assert_eq!(a.clone() * id, a);
+    }
+
+    #[test]
+    fn test_transpose() {
+        let a = DMatrix::from_row_slice(4, 4, &[0, 9, 3, 0, 9, 8, 0, 8, 1, 8, 5, 3, 0, 0, 5, 8]);
+        let t = a.transpose();
+        assert_eq!(
+            t,
+            DMatrix::from_row_slice(4, 4, &[0, 9, 1, 0, 9, 8, 8, 0, 3, 0, 5, 5, 0, 8, 3, 8])
+        );
+    }
+
+    #[test]
+    fn test_id_transpose() {
+        let id: Matrix<i64, Dynamic, Dynamic, VecStorage<i64, Dynamic, Dynamic>> =
+            DMatrix::identity(4, 4);
+        assert_eq!(id.clone(), id.transpose());
+    }
+
+    #[test]
+    fn test_determinant() {
+        let a = DMatrix::from_row_slice(2, 2, &[1.0, 5.0, -3.0, 2.0]);
+        assert_eq!(a.determinant(), 17.0);
+    }
+
+    #[test]
+    fn test_submatrix() {
+        let a = DMatrix::from_row_slice(3, 3, &[1.0, 5.0, 0.0, -3.0, 2.0, 7.0, 0.0, 6.0, -3.0]);
+        assert_eq!(
+            submatrix(&a, 0, 2),
+            DMatrix::from_row_slice(2, 2, &[-3.0, 2.0, 0.0, 6.0])
+        );
+    }
+
+    #[test]
+    fn test_submatrix_2() {
+        let a = DMatrix::from_row_slice(
+            4,
+            4,
+            &[
+                -6.0, 1.0, 1.0, 6.0, -8.0, 5.0, 8.0, 6.0, -1.0, 0.0, 8.0, 2.0, -7.0, 1.0, -1.0, 1.0,
+            ],
+        );
+        assert_eq!(
+            submatrix(&a, 2, 1),
+            DMatrix::from_row_slice(3, 3, &[-6.0, 1.0, 6.0, -8.0, 8.0, 6.0, -7.0, -1.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn test_minor() {
+        let a = DMatrix::from_row_slice(3, 3, &[3.0, 5.0, 0.0, 2.0, -1.0, -7.0, 6.0, -1.0, 5.0]);
+        let b = submatrix(&a, 1, 0);
+        assert_eq!(b.determinant(), 25.0);
+        assert_eq!(minor(&a, 1, 0), 25.0);
+    }
+
+    #[test]
+    fn test_cofactor() {
+        let a = DMatrix::from_row_slice(3, 3, &[3.0, 5.0, 0.0, 2.0, -1.0, -7.0, 6.0, -1.0, 5.0]);
+        assert_eq!(minor(&a, 0, 0), -12.0);
+        assert_eq!(cofactor(&a, 0, 0), -12.0);
+        assert_eq!(minor(&a, 1, 0), 25.0);
+        assert_eq!(cofactor(&a, 1, 0), -25.0);
+    }
+
+    #[test]
+    fn test_cofactor_m3() {
+        let a = DMatrix::from_row_slice(3, 3, &[1.0, 2.0, 6.0, -5.0, 8.0, -4.0, 2.0, 6.0, 4.0]);
+        assert_eq!(cofactor(&a, 0, 0), 56.0);
+        assert_eq!(cofactor(&a, 0, 1), 12.0);
+        assert_eq!(cofactor(&a, 0, 2), -46.0);
+        assert_eq!(a.determinant(), -196.0);
+    }
+
+    #[test]
+    fn test_cofactor_m4() {
+        let a = DMatrix::from_row_slice(
+            4,
+            4,
+            &[
+                -2.0, -8.0, 3.0, 5.0, -3.0, 1.0, 7.0, 3.0, 1.0, 2.0, -9.0, 6.0, -6.0, 7.0, 7.0,
+                -9.0,
+            ],
+        );
+        assert_eq!(cofactor(&a, 0, 0), 690.0);
+        assert_eq!(cofactor(&a, 0, 1), 447.0);
+        assert_eq!(cofactor(&a, 0, 2), 210.0);
+        assert_eq!(cofactor(&a, 0, 3), 51.0);
+        assert_relative_eq!(a.determinant(), -4071.0);
+    }
+
+    #[test]
+    fn test_invertible() {
+        let a = DMatrix::from_row_slice(
+            4,
+            4,
+            &[
+                6.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 6.0, 4.0, -9.0, 3.0, -7.0, 9.0, 1.0, 7.0, -6.0,
+            ],
+        );
+
+        assert_eq!(a.determinant(), -2120.0);
+        assert_eq!(a.is_invertible(), true);
+    }
+
+    #[test]
+    fn test_not_invertible() {
+        let a = DMatrix::from_row_slice(
+            4,
+            4,
+            &[
+                -4.0, 2.0, -2.0, -3.0, 9.0, 6.0, 2.0, 6.0, 0.0, -5.0, 1.0, -5.0, 0.0, 0.0, 0.0, 0.0,
+            ],
+        );
+
+        assert_eq!(a.determinant(), 0.0);
+        assert_eq!(a.is_invertible(), false);
+    }
+
+    #[test]
+    fn test_not_inverse() {
+        let a = DMatrix::from_row_slice(
+            4,
+            4,
+            &[
+                -5.0, 2.0, 6.0, -8.0, 1.0, -5.0, 1.0, 8.0, 7.0, 7.0, -6.0, -7.0, 1.0, -3.0, 7.0,
+                4.0,
+            ],
+        );
+        let b = a.clone().try_inverse().unwrap();
+        assert_relative_eq!(a.determinant(), 532.0);
+        assert_eq!(cofactor(&a, 2, 3), -160.0);
+        assert_eq!(b[(3, 2)], -160.0 / 532.0);
+        assert_eq!(cofactor(&a, 3, 2), 105.0);
+        assert_eq!(b[(2, 3)], 105.0 / 532.0);
+        assert_relative_eq!(
+            b,
+            DMatrix::from_row_slice(
+                4,
+                4,
+                #[cfg_attr(rustfmt, rustfmt_skip)]
+                &[
+                    0.21804511278195488,                    0.45112781954887216,                   0.24060150375939848,                    -0.045112781954887216,
+                    -0.8082706766917293,                    -1.456766917293233,                    -0.44360902255639095,                    0.5206766917293233,
+                    -0.07894736842105263,                   -0.22368421052631576,                  -0.05263157894736842,                    0.19736842105263158,
+                    -0.5225563909774436,                    -0.8139097744360901,                   -0.3007518796992481,                     0.306390977443609
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_inverting_id() {
+        let id: Matrix<f64, Dynamic, Dynamic, VecStorage<f64, Dynamic, Dynamic>> =
+            DMatrix::identity(4, 4);
+        if PRINT_NOTES {
+            println!("what is the inverse of the identity matrix?");
+            println!("id     : {:?}", id.clone());
+            println!("inverse: {:?}", id.clone().try_inverse().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_translation() {
+        let m = translation(4.0, 3.0, 2.0);
+        assert_eq!(
+            #[cfg_attr(rustfmt, rustfmt_skip)]
+            Matrix4::from_row_slice(&[
+                1.0, 0.0, 0.0, 4.0,
+                0.0, 1.0, 0.0, 3.0,
+                0.0, 0.0, 1.0, 2.0,
+                0.0, 0.0, 0.0, 1.0,
+            ]),
+            m
+        );
+    }
+
+    #[test]
+    fn test_transforming_a_point_by_a_translation_matrix() {
+        let t = translation(5.0, -3.0, 2.0);
+        let p = Point3::new(-3.0, 4.0, 5.0);
+        // The point goes forwards!
+        assert_eq!(t.transform_point(&p), Point3::new(2.0, 1.0, 7.0));
+    }
+
+    #[test]
+    fn test_transforming_a_point_by_the_inverse_of_a_translation_matrix() {
+        let mut t = translation(5.0, -3.0, 2.0);
+        t.try_inverse_mut();
+        let p = Point3::new(-3.0, 4.0, 5.0);
+        // The point goes backwards?!?!?
+        assert_eq!(t.transform_point(&p), Point3::new(-8.0, 7.0, 3.0));
+    }
+
+    #[test]
+    fn test_translation_does_not_affect_vectors() {
+        let t = translation(5.0, -3.0, 2.0);
+        let v = Vector3::new(-3.0, 4.0, 5.0);
+        // translation does not affect vectors
+        assert_eq!(t.transform_vector(&v), v);
+    }
+
+    #[test]
+    fn test_scaling_a_matrix_applied_to_a_point() {
+        let t = scaling(2.0, 3.0, 4.0);
+        let p = Point3::new(-4.0, 6.0, 8.0);
+        // scaling scales out all points
+        assert_eq!(t.transform_point(&p), Point3::new(-8.0, 18.0, 32.0));
+    }
+
+    #[test]
+    fn test_scaling_matrix_applied_to_a_vector() {
+        let t = scaling(2.0, 3.0, 4.0);
+        let v = Vector3::new(-4.0, 6.0, 8.0);
+        // scaling scales vectors as well
+        assert_eq!(t.transform_vector(&v), Vector3::new(-8.0, 18.0, 32.0));
+    }
+
+    #[test]
+    fn test_reflection_is_scaling_by_a_negative_value() {
+        let t = scaling(-1.0, 1.0, 1.0);
+        let p = Point3::new(2.0, 3.0, 4.0);
+        // reflects a point over the x axis
+        assert_eq!(t.transform_point(&p), Point3::new(-2.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn test_rotating_a_point_around_the_x_axis() {
+        let p = Point3::new(0.0, 1.0, 0.0);
+        let half_quarter = rotation_x(std::f64::consts::FRAC_PI_4);
+        let full_quarter = rotation_x(std::f64::consts::FRAC_PI_2);
+        assert_relative_eq!(
+            half_quarter.transform_point(&p),
+            Point3::new(0.0, (2.0).sqrt() / 2.0, (2.0).sqrt() / 2.0)
+        );
+        assert_relative_eq!(full_quarter.transform_point(&p), Point3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_rotating_a_point_around_the_y_axis() {
+        let p = Point3::new(0.0, 0.0, 1.0);
+        let half_quarter = rotation_y(std::f64::consts::FRAC_PI_4);
+        let full_quarter = rotation_y(std::f64::consts::FRAC_PI_2);
+        assert_relative_eq!(
+            half_quarter.transform_point(&p),
+            Point3::new((2.0).sqrt() / 2.0, 0.0, (2.0).sqrt() / 2.0)
+        );
+        assert_relative_eq!(full_quarter.transform_point(&p), Point3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_rotating_a_point_on_the_x_axis_around_the_y_axis() {
+        let p = Point3::new(1.0, 0.0, 0.0);
+        let full_quarter = rotation_y(std::f64::consts::FRAC_PI_2);
+        assert_relative_eq!(
+            full_quarter.transform_point(&p),
+            Point3::new(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn test_rotating_a_point_around_the_z_axis() {
+        let p = Point3::new(0.0, 1.0, 0.0);
+        let half_quarter = rotation_z(std::f64::consts::FRAC_PI_4);
+        let full_quarter = rotation_z(std::f64::consts::FRAC_PI_2);
+        assert_relative_eq!(
+            half_quarter.transform_point(&p),
+            Point3::new(-1.0 * (2.0).sqrt() / 2.0, (2.0).sqrt() / 2.0, 0.0)
+        );
+        assert_relative_eq!(
+            full_quarter.transform_point(&p),
+            Point3::new(-1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_shearing_x_in_proportion_to_y() {
+        let transform = shearing(
+            1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0
+        );
+        let p = Point3::new(2.0, 3.0, 4.0);
+        assert_relative_eq!(
+            transform.transform_point(&p),
+            Point3::new(5.0, 3.0, 4.0)
+        )
+    }
+
+    #[test]
+    fn test_shearing_x_in_proportion_to_z() {
+        let transform = shearing(
+            0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0
+        );
+        let p = Point3::new(2.0, 3.0, 4.0);
+        assert_relative_eq!(
+            transform.transform_point(&p),
+            Point3::new(6.0, 3.0, 4.0)
+        )
+    }
+
+    #[test]
+    fn test_shearing_y_in_proportion_to_x() {
+        let transform = shearing(
+            0.0, 0.0, 1.0,
+            0.0, 0.0, 0.0
+        );
+        let p = Point3::new(2.0, 3.0, 4.0);
+        assert_relative_eq!(
+            transform.transform_point(&p),
+            Point3::new(2.0, 5.0, 4.0)
+        )
+    }
+
+    #[test]
+    fn test_shearing_y_in_proportion_to_z() {
+        let transform = shearing(
+            0.0, 0.0, 0.0,
+            1.0, 0.0, 0.0
+        );
+        let p = Point3::new(2.0, 3.0, 4.0);
+        assert_relative_eq!(
+            transform.transform_point(&p),
+            Point3::new(2.0, 7.0, 4.0)
+        )
+    }
+
+    #[test]
+    fn test_shearing_z_in_proportion_to_x() {
+        let transform = shearing(
+            0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0
+        );
+        let p = Point3::new(2.0, 3.0, 4.0);
+        assert_relative_eq!(
+            transform.transform_point(&p),
+            Point3::new(2.0, 3.0, 6.0)
+        )
+    }
+
+    #[test]
+    fn test_shearing_z_in_proportion_to_y() {
+        let transform = shearing(
+            0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0
+        );
+        let p = Point3::new(2.0, 3.0, 4.0);
+        assert_relative_eq!(
+            transform.transform_point(&p),
+            Point3::new(2.0, 3.0, 7.0)
+        )
+    }
+
+    #[test]
+    fn test_creating_and_querying_a_ray() {
+        let origin = Tuple::point(1.0, 2.0, 3.0);
+        let direction = Tuple::vector(4.0, 5.0, 6.0);
+        let r = ray(origin, direction);
+        assert_eq!(r.origin, origin);
+        assert_eq!(r.direction, direction);
+    }
+
+    #[test]
+    fn test_computing_a_point_from_a_distance() {
+        let r = ray(Tuple::point(2.0, 3.0, 4.0), Tuple::vector(1.0, 0.0, 0.0));
+        assert_eq!(r.position(0.0), Tuple::point(2.0, 3.0, 4.0));
+        assert_eq!(r.position(1.0), Tuple::point(3.0, 3.0, 4.0));
+        assert_eq!(r.position(-1.0), Tuple::point(1.0, 3.0, 4.0));
+        assert_eq!(r.position(2.5), Tuple::point(4.5, 3.0, 4.0));
+    }
+
+    #[test]
+    fn test_translating_a_ray() {
+        let r = ray(Tuple::point(1.0, 2.0, 3.0), Tuple::vector(0.0, 1.0, 0.0));
+        let m = translation(3.0, 4.0, 5.0);
+        let r2 = r.transform(&m);
+        assert_eq!(r2.origin, Tuple::point(4.0, 6.0, 8.0));
+        // the direction is a vector, so it is left alone
+        assert_eq!(r2.direction, Tuple::vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_scaling_a_ray() {
+        let r = ray(Tuple::point(1.0, 2.0, 3.0), Tuple::vector(0.0, 1.0, 0.0));
+        let m = scaling(2.0, 3.0, 4.0);
+        let r2 = r.transform(&m);
+        assert_eq!(r2.origin, Tuple::point(2.0, 6.0, 12.0));
+        // scaling is not normalized away
+        assert_eq!(r2.direction, Tuple::vector(0.0, 3.0, 0.0));
+    }
+
+    #[test]
+    fn test_rotating_a_ray() {
+        let r = ray(Tuple::point(0.0, 1.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        let r2 = r.transform(&rotation_x(std::f64::consts::FRAC_PI_2));
+        assert_relative_eq!(r2.origin, Tuple::point(0.0, 0.0, 1.0));
+        assert_relative_eq!(r2.direction, Tuple::vector(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_a_ray_intersects_a_sphere_at_two_points() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq!(xs[1].t, 6.0);
+    }
+
+    #[test]
+    fn test_a_ray_intersects_a_sphere_at_a_tangent() {
+        let r = ray(Tuple::point(0.0, 1.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 5.0);
+        assert_eq!(xs[1].t, 5.0);
+    }
+
+    #[test]
+    fn test_a_ray_misses_a_sphere() {
+        let r = ray(Tuple::point(0.0, 2.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        assert!(s.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn test_a_ray_originates_inside_a_sphere() {
+        let r = ray(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, -1.0);
+        assert_eq!(xs[1].t, 1.0);
+    }
+
+    #[test]
+    fn test_a_sphere_is_behind_a_ray() {
+        let r = ray(Tuple::point(0.0, 0.0, 5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, -6.0);
+        assert_eq!(xs[1].t, -4.0);
+    }
+
+    #[test]
+    fn test_a_spheres_default_transformation() {
+        let s = sphere();
+        assert_eq!(s.transform, Matrix4::identity());
+    }
+
+    #[test]
+    fn test_intersecting_a_scaled_sphere_with_a_ray() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut s = sphere();
+        s.transform = scaling(2.0, 2.0, 2.0);
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 3.0);
+        assert_eq!(xs[1].t, 7.0);
+    }
+
+    #[test]
+    fn test_intersecting_a_translated_sphere_with_a_ray() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut s = sphere();
+        s.transform = translation(5.0, 0.0, 0.0);
+        assert!(s.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn test_intersecting_a_sphere_with_a_singular_transform() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut s = sphere();
+        s.transform = scaling(0.0, 1.0, 1.0);
+        assert!(s.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn test_intersect_sets_the_object_on_the_intersection() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let s = sphere();
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].object, &s as &dyn Shape);
+        assert_eq!(xs[1].object, &s as &dyn Shape);
+    }
+
+    #[test]
+    fn test_an_intersection_encapsulates_t_and_object() {
+        let s = sphere();
+        let i = intersection(3.5, &s);
+        assert_eq!(i.t, 3.5);
+        assert_eq!(i.object, &s as &dyn Shape);
+    }
+
+    #[test]
+    fn test_aggregating_intersections() {
+        let s = sphere();
+        let xs = intersections(vec![intersection(2.0, &s), intersection(1.0, &s)]);
+        // kept sorted regardless of insertion order
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 1.0);
+        assert_eq!(xs[1].t, 2.0);
+    }
+
+    #[test]
+    fn test_the_hit_when_all_intersections_have_positive_t() {
+        let s = sphere();
+        let i1 = intersection(1.0, &s);
+        let i2 = intersection(2.0, &s);
+        let xs = intersections(vec![i2, i1]);
+        assert_eq!(xs.hit(), Some(&i1));
+    }
+
+    #[test]
+    fn test_the_hit_when_some_intersections_have_negative_t() {
+        let s = sphere();
+        let i1 = intersection(-1.0, &s);
+        let i2 = intersection(1.0, &s);
+        let xs = intersections(vec![i2, i1]);
+        assert_eq!(xs.hit(), Some(&i2));
+    }
+
+    #[test]
+    fn test_the_hit_when_all_intersections_have_negative_t() {
+        let s = sphere();
+        let i1 = intersection(-2.0, &s);
+        let i2 = intersection(-1.0, &s);
+        let xs = intersections(vec![i2, i1]);
+        assert_eq!(xs.hit(), None);
+    }
+
+    #[test]
+    fn test_the_hit_is_always_the_lowest_nonnegative_intersection() {
+        let s = sphere();
+        let i1 = intersection(5.0, &s);
+        let i2 = intersection(7.0, &s);
+        let i3 = intersection(-3.0, &s);
+        let i4 = intersection(2.0, &s);
+        let xs = intersections(vec![i1, i2, i3, i4]);
+        assert_eq!(xs.hit(), Some(&i4));
+    }
+
+    #[test]
+    fn test_the_normal_on_a_sphere_at_a_point_on_the_x_axis() {
+        let s = sphere();
+        let n = s.normal_at(Tuple::point(1.0, 0.0, 0.0), &intersection(0.0, &s));
+        assert_eq!(n, Tuple::vector(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_the_normal_on_a_sphere_at_a_point_on_the_y_axis() {
+        let s = sphere();
+        let n = s.normal_at(Tuple::point(0.0, 1.0, 0.0), &intersection(0.0, &s));
+        assert_eq!(n, Tuple::vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_the_normal_on_a_sphere_at_a_point_on_the_z_axis() {
+        let s = sphere();
+        let n = s.normal_at(Tuple::point(0.0, 0.0, 1.0), &intersection(0.0, &s));
+        assert_eq!(n, Tuple::vector(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_the_normal_on_a_sphere_at_a_nonaxial_point() {
+        let s = sphere();
+        let k = 3.0_f64.sqrt() / 3.0;
+        let n = s.normal_at(Tuple::point(k, k, k), &intersection(0.0, &s));
+        assert_relative_eq!(n, Tuple::vector(k, k, k));
+    }
+
+    #[test]
+    fn test_the_normal_is_a_normalized_vector() {
+        let s = sphere();
+        let k = 3.0_f64.sqrt() / 3.0;
+        let n = s.normal_at(Tuple::point(k, k, k), &intersection(0.0, &s));
+        assert_relative_eq!(n, normalize(n));
+    }
+
+    #[test]
+    fn test_computing_the_normal_on_a_translated_sphere() {
+        let mut s = sphere();
+        s.transform = translation(0.0, 1.0, 0.0);
+        let k = std::f64::consts::FRAC_1_SQRT_2;
+        let n = s.normal_at(Tuple::point(0.0, 1.0 + k, -k), &intersection(0.0, &s));
+        assert_relative_eq!(n, Tuple::vector(0.0, k, -k));
+    }
+
+    #[test]
+    fn test_computing_the_normal_on_a_transformed_sphere() {
+        let mut s = sphere();
+        s.transform = scaling(1.0, 0.5, 1.0) * rotation_z(std::f64::consts::PI / 5.0);
+        let k = 2.0_f64.sqrt() / 2.0;
+        let n = s.normal_at(Tuple::point(0.0, k, -k), &intersection(0.0, &s));
+        assert_relative_eq!(n, Tuple::vector(0.0, 0.97014, -0.24254));
+    }
+
+    #[test]
+    fn test_reflecting_a_vector_approaching_at_45_degrees() {
+        let v = Tuple::vector(1.0, -1.0, 0.0);
+        let n = Tuple::vector(0.0, 1.0, 0.0);
+        assert_eq!(reflect(v, n), Tuple::vector(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_reflecting_a_vector_off_a_slanted_surface() {
+        let v = Tuple::vector(0.0, -1.0, 0.0);
+        let k = 2.0_f64.sqrt() / 2.0;
+        let n = Tuple::vector(k, k, 0.0);
+        assert_relative_eq!(reflect(v, n), Tuple::vector(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_a_point_light_has_a_position_and_intensity() {
+        let intensity = color(1.0, 1.0, 1.0);
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let light = point_light(position, intensity);
+        assert_eq!(light.position, position);
+        assert_relative_eq!(light.intensity, intensity);
+    }
+
+    #[test]
+    fn test_the_default_material() {
+        let m = material();
+        assert_relative_eq!(m.color, color(1.0, 1.0, 1.0));
+        assert_eq!(m.ambient, 0.1);
+        assert_eq!(m.diffuse, 0.9);
+        assert_eq!(m.specular, 0.9);
+        assert_eq!(m.shininess, 200.0);
+    }
+
+    #[test]
+    fn test_a_sphere_may_be_assigned_a_material() {
+        let mut s = sphere();
+        assert_eq!(s.material, material());
+        let mut m = material();
+        m.ambient = 1.0;
+        s.material = m;
+        assert_eq!(s.material, m);
+    }
+
+    #[test]
+    fn test_lighting_with_the_eye_between_the_light_and_the_surface() {
+        let m = material();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = point_light(Tuple::point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0));
+        let result = lighting(&m, &sphere(), &light, position, eyev, normalv, false);
+        assert_relative_eq!(result, color(1.9, 1.9, 1.9));
+    }
+
+    #[test]
+    fn test_lighting_with_the_eye_between_light_and_surface_eye_offset_45_degrees() {
+        let m = material();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let k = 2.0_f64.sqrt() / 2.0;
+        let eyev = Tuple::vector(0.0, k, -k);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = point_light(Tuple::point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0));
+        let result = lighting(&m, &sphere(), &light, position, eyev, normalv, false);
+        assert_relative_eq!(result, color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_lighting_with_eye_opposite_surface_light_offset_45_degrees() {
+        let m = material();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = point_light(Tuple::point(0.0, 10.0, -10.0), color(1.0, 1.0, 1.0));
+        let result = lighting(&m, &sphere(), &light, position, eyev, normalv, false);
+        assert_relative_eq!(result, color(0.7364, 0.7364, 0.7364));
+    }
+
+    #[test]
+    fn test_lighting_with_eye_in_the_path_of_the_reflection_vector() {
+        let m = material();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let k = 2.0_f64.sqrt() / 2.0;
+        let eyev = Tuple::vector(0.0, -k, -k);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = point_light(Tuple::point(0.0, 10.0, -10.0), color(1.0, 1.0, 1.0));
+        let result = lighting(&m, &sphere(), &light, position, eyev, normalv, false);
+        assert_relative_eq!(result, color(1.6364, 1.6364, 1.6364));
+    }
+
+    #[test]
+    fn test_lighting_with_the_light_behind_the_surface() {
+        let m = material();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = point_light(Tuple::point(0.0, 0.0, 10.0), color(1.0, 1.0, 1.0));
+        let result = lighting(&m, &sphere(), &light, position, eyev, normalv, false);
+        assert_relative_eq!(result, color(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn test_creating_a_world() {
+        let w = world();
+        assert!(w.objects.is_empty());
+        assert!(w.lights.is_empty());
+    }
+
+    #[test]
+    fn test_the_default_world() {
+        let w = default_world();
+        let light = point_light(Tuple::point(-10.0, 10.0, -10.0), color(1.0, 1.0, 1.0));
+        assert_eq!(w.lights, vec![light]);
+        assert_eq!(w.objects.len(), 2);
+        assert_relative_eq!(w.objects[0].material().color, color(0.8, 1.0, 0.6));
+        assert_eq!(w.objects[1].transform(), &scaling(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_intersect_a_world_with_a_ray() {
+        let w = default_world();
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = intersect_world(&w, &r);
+        assert_eq!(xs.len(), 4);
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq!(xs[1].t, 4.5);
+        assert_eq!(xs[2].t, 5.5);
+        assert_eq!(xs[3].t, 6.0);
+    }
+
+    #[test]
+    fn test_precomputing_the_state_of_an_intersection() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let shape = sphere();
+        let i = intersection(4.0, &shape);
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_eq!(comps.t, i.t);
+        assert_eq!(comps.object, &shape as &dyn Shape);
+        assert_eq!(comps.point, Tuple::point(0.0, 0.0, -1.0));
+        assert_eq!(comps.eyev, Tuple::vector(0.0, 0.0, -1.0));
+        assert_eq!(comps.normalv, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_the_hit_when_an_intersection_occurs_on_the_outside() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let shape = sphere();
+        let i = intersection(4.0, &shape);
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert!(!comps.inside);
+    }
+
+    #[test]
+    fn test_the_hit_when_an_intersection_occurs_on_the_inside() {
+        let r = ray(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let shape = sphere();
+        let i = intersection(1.0, &shape);
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_eq!(comps.point, Tuple::point(0.0, 0.0, 1.0));
+        assert_eq!(comps.eyev, Tuple::vector(0.0, 0.0, -1.0));
+        assert!(comps.inside);
+        // the normal is inverted to face the eye
+        assert_eq!(comps.normalv, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_the_hit_should_offset_the_point() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut shape = sphere();
+        shape.transform = translation(0.0, 0.0, 1.0);
+        let i = intersection(5.0, &shape);
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert!(comps.over_point.z < -ERR / 2.0);
+        assert!(comps.point.z > comps.over_point.z);
+    }
+
+    #[test]
+    fn test_shading_an_intersection() {
+        let w = default_world();
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let i = intersection(4.0, w.objects[0].as_ref());
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_relative_eq!(
+            shade_hit(&w, &comps, DEFAULT_MAX_DEPTH),
+            color(0.38066, 0.47583, 0.2855)
+        );
+    }
+
+    #[test]
+    fn test_shading_an_intersection_from_the_inside() {
+        let mut w = default_world();
+        w.lights = vec![point_light(
+            Tuple::point(0.0, 0.25, 0.0),
+            color(1.0, 1.0, 1.0),
+        )];
+        let r = ray(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let i = intersection(0.5, w.objects[1].as_ref());
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_relative_eq!(
+            shade_hit(&w, &comps, DEFAULT_MAX_DEPTH),
+            color(0.90498, 0.90498, 0.90498)
+        );
+    }
+
+    #[test]
+    fn test_the_color_when_a_ray_misses() {
+        let w = default_world();
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 1.0, 0.0));
+        assert_relative_eq!(color_at(&w, &r, DEFAULT_MAX_DEPTH), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_the_color_when_a_ray_hits() {
+        let w = default_world();
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_relative_eq!(
+            color_at(&w, &r, DEFAULT_MAX_DEPTH),
+            color(0.38066, 0.47583, 0.2855)
+        );
+    }
+
+    #[test]
+    fn test_the_color_with_an_intersection_behind_the_ray() {
+        let mut w = default_world();
+        let mut outer = sphere();
+        outer.material.ambient = 1.0;
+        let mut inner = sphere();
+        inner.transform = scaling(0.5, 0.5, 0.5);
+        inner.material.ambient = 1.0;
+        let expected = inner.material.color;
+        w.objects = vec![Box::new(outer), Box::new(inner)];
+        let r = ray(Tuple::point(0.0, 0.0, 0.75), Tuple::vector(0.0, 0.0, -1.0));
+        // the ray starts between the spheres, so it sees the inner one
+        assert_relative_eq!(color_at(&w, &r, DEFAULT_MAX_DEPTH), expected);
+    }
+
+    #[test]
+    fn test_the_transformation_matrix_for_the_default_orientation() {
+        let from = Tuple::point(0.0, 0.0, 0.0);
+        let to = Tuple::point(0.0, 0.0, -1.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        assert_eq!(view_transform(from, to, up), Matrix4::identity());
+    }
+
+    #[test]
+    fn test_a_view_transformation_matrix_looking_in_positive_z_direction() {
+        let from = Tuple::point(0.0, 0.0, 0.0);
+        let to = Tuple::point(0.0, 0.0, 1.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        // looking backwards mirrors the world front to back and left to right
+        assert_eq!(view_transform(from, to, up), scaling(-1.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn test_the_view_transformation_moves_the_world() {
+        let from = Tuple::point(0.0, 0.0, 8.0);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        assert_eq!(view_transform(from, to, up), translation(0.0, 0.0, -8.0));
+    }
+
+    #[test]
+    fn test_an_arbitrary_view_transformation() {
+        let from = Tuple::point(1.0, 3.0, 2.0);
+        let to = Tuple::point(4.0, -2.0, 8.0);
+        let up = Tuple::vector(1.0, 1.0, 0.0);
+        assert_relative_eq!(
+            view_transform(from, to, up),
+            #[cfg_attr(rustfmt, rustfmt_skip)]
+            Matrix4::from_row_slice(&[
+                -0.50709, 0.50709,  0.67612, -2.36643,
+                0.76772,  0.60609,  0.12122, -2.82843,
+                -0.35857, 0.59761,  -0.71714, 0.00000,
+                0.00000,  0.00000,  0.00000,  1.00000,
+            ]),
+            epsilon = ERR
+        );
+    }
+
+    #[test]
+    fn test_constructing_a_camera() {
+        let c = camera(160, 120, std::f64::consts::FRAC_PI_2);
+        assert_eq!(c.hsize, 160);
+        assert_eq!(c.vsize, 120);
+        assert_eq!(c.field_of_view, std::f64::consts::FRAC_PI_2);
+        assert_eq!(c.transform, Matrix4::identity());
+    }
+
+    #[test]
+    fn test_the_pixel_size_for_a_horizontal_canvas() {
+        let c = camera(200, 125, std::f64::consts::FRAC_PI_2);
+        assert_relative_eq!(c.pixel_size(), 0.01);
+    }
+
+    #[test]
+    fn test_the_pixel_size_for_a_vertical_canvas() {
+        let c = camera(125, 200, std::f64::consts::FRAC_PI_2);
+        assert_relative_eq!(c.pixel_size(), 0.01);
+    }
+
+    #[test]
+    fn test_constructing_a_ray_through_the_center_of_the_canvas() {
+        let c = camera(201, 101, std::f64::consts::FRAC_PI_2);
+        let r = c.ray_for_pixel(100, 50);
+        assert_relative_eq!(r.origin, Tuple::point(0.0, 0.0, 0.0));
+        assert_relative_eq!(r.direction, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_constructing_a_ray_through_a_corner_of_the_canvas() {
+        let c = camera(201, 101, std::f64::consts::FRAC_PI_2);
+        let r = c.ray_for_pixel(0, 0);
+        assert_relative_eq!(r.origin, Tuple::point(0.0, 0.0, 0.0));
+        assert_relative_eq!(r.direction, Tuple::vector(0.66519, 0.33259, -0.66851));
+    }
+
+    #[test]
+    fn test_constructing_a_ray_when_the_camera_is_transformed() {
+        let mut c = camera(201, 101, std::f64::consts::FRAC_PI_2);
+        c.transform = rotation_y(std::f64::consts::FRAC_PI_4) * translation(0.0, -2.0, 5.0);
+        let r = c.ray_for_pixel(100, 50);
+        let k = 2.0_f64.sqrt() / 2.0;
+        assert_relative_eq!(r.origin, Tuple::point(0.0, 2.0, -5.0));
+        assert_relative_eq!(r.direction, Tuple::vector(k, 0.0, -k));
+    }
+
+    #[test]
+    fn test_rendering_a_world_with_a_camera() {
+        let w = default_world();
+        let mut c = camera(11, 11, std::f64::consts::FRAC_PI_2);
+        let from = Tuple::point(0.0, 0.0, -5.0);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        c.transform = view_transform(from, to, up);
+        let image = c.render(&w);
+        assert_relative_eq!(pixel_at(&image, 5, 5), color(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn test_lighting_with_the_surface_in_shadow() {
+        let m = material();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = point_light(Tuple::point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0));
+        let result = lighting(&m, &sphere(), &light, position, eyev, normalv, true);
+        assert_relative_eq!(result, color(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn test_there_is_no_shadow_when_nothing_is_collinear_with_point_and_light() {
+        let w = default_world();
+        let p = Tuple::point(0.0, 10.0, 0.0);
+        assert!(!is_shadowed(&w, &w.lights[0], p));
+    }
+
+    #[test]
+    fn test_the_shadow_when_an_object_is_between_the_point_and_the_light() {
+        let w = default_world();
+        let p = Tuple::point(10.0, -10.0, 10.0);
+        assert!(is_shadowed(&w, &w.lights[0], p));
+    }
+
+    #[test]
+    fn test_there_is_no_shadow_when_an_object_is_behind_the_light() {
+        let w = default_world();
+        let p = Tuple::point(-20.0, 20.0, -20.0);
+        assert!(!is_shadowed(&w, &w.lights[0], p));
+    }
+
+    #[test]
+    fn test_there_is_no_shadow_when_an_object_is_behind_the_point() {
+        let w = default_world();
+        let p = Tuple::point(-2.0, 2.0, -2.0);
+        assert!(!is_shadowed(&w, &w.lights[0], p));
+    }
+
+    #[test]
+    fn test_shade_hit_is_given_an_intersection_in_shadow() {
+        let mut w = world();
+        w.lights = vec![point_light(
+            Tuple::point(0.0, 0.0, -10.0),
+            color(1.0, 1.0, 1.0),
+        )];
+        let s1 = sphere();
+        let mut s2 = sphere();
+        s2.transform = translation(0.0, 0.0, 10.0);
+        w.objects = vec![Box::new(s1), Box::new(s2)];
+        let r = ray(Tuple::point(0.0, 0.0, 5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let i = intersection(4.0, w.objects[1].as_ref());
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_relative_eq!(
+            shade_hit(&w, &comps, DEFAULT_MAX_DEPTH),
+            color(0.1, 0.1, 0.1)
+        );
+    }
+
+    // records the object-space ray it was asked to intersect
+    #[derive(Debug)]
+    struct TestShape {
+        transform: Matrix4<f64>,
+        parent_transform: Matrix4<f64>,
+        material: Material,
+        saved_ray: std::cell::Cell<Option<Ray>>,
+    }
+
+    fn test_shape() -> TestShape {
+        TestShape {
+            transform: Matrix4::identity(),
+            parent_transform: Matrix4::identity(),
+            material: material(),
+            saved_ray: std::cell::Cell::new(None),
+        }
+    }
+
+    impl Shape for TestShape {
+        fn transform(&self) -> &Matrix4<f64> {
+            &self.transform
+        }
+        fn material(&self) -> &Material {
+            &self.material
+        }
+        fn parent_transform(&self) -> &Matrix4<f64> {
+            &self.parent_transform
+        }
+        fn set_parent_transform(&mut self, m: Matrix4<f64>) {
+            self.parent_transform = m;
+        }
+        fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>> {
+            self.saved_ray.set(Some(*r));
+            vec![]
+        }
+        fn local_normal_at(&self, p: Tuple, _hit: &Intersection) -> Tuple {
+            Tuple::vector(p.x, p.y, p.z)
+        }
+        fn bounds(&self) -> BoundingBox {
+            bounding_box(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0))
+        }
+    }
+
+    #[test]
+    fn test_the_default_shape_transformation_and_material() {
+        let s = test_shape();
+        assert_eq!(s.transform(), &Matrix4::identity());
+        assert_eq!(s.material(), &material());
+    }
+
+    #[test]
+    fn test_intersecting_a_scaled_shape_with_a_ray() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut s = test_shape();
+        s.transform = scaling(2.0, 2.0, 2.0);
+        s.intersect(&r);
+        let saved = s.saved_ray.get().unwrap();
+        assert_eq!(saved.origin, Tuple::point(0.0, 0.0, -2.5));
+        assert_eq!(saved.direction, Tuple::vector(0.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn test_intersecting_a_translated_shape_with_a_ray() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut s = test_shape();
+        s.transform = translation(5.0, 0.0, 0.0);
+        s.intersect(&r);
+        let saved = s.saved_ray.get().unwrap();
+        assert_eq!(saved.origin, Tuple::point(-5.0, 0.0, -5.0));
+        assert_eq!(saved.direction, Tuple::vector(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_computing_the_normal_on_a_translated_shape() {
+        let mut s = test_shape();
+        s.transform = translation(0.0, 1.0, 0.0);
+        let k = std::f64::consts::FRAC_1_SQRT_2;
+        let n = s.normal_at(Tuple::point(0.0, 1.0 + k, -k), &intersection(0.0, &s));
+        assert_relative_eq!(n, Tuple::vector(0.0, k, -k));
+    }
+
+    #[test]
+    fn test_computing_the_normal_on_a_transformed_shape() {
+        let mut s = test_shape();
+        s.transform = scaling(1.0, 0.5, 1.0) * rotation_z(std::f64::consts::PI / 5.0);
+        let k = 2.0_f64.sqrt() / 2.0;
+        let n = s.normal_at(Tuple::point(0.0, k, -k), &intersection(0.0, &s));
+        assert_relative_eq!(n, Tuple::vector(0.0, 0.97014, -0.24254));
+    }
+
+    #[test]
+    fn test_shapes_are_compared_by_identity() {
+        let a = sphere();
+        let b = sphere();
+        assert_eq!(&a as &dyn Shape, &a as &dyn Shape);
+        assert_ne!(&a as &dyn Shape, &b as &dyn Shape);
+    }
+
+    #[test]
+    fn test_the_normal_of_a_plane_is_constant_everywhere() {
+        let p = plane();
+        let hit = intersection(0.0, &p);
+        assert_eq!(
+            p.local_normal_at(Tuple::point(0.0, 0.0, 0.0), &hit),
+            Tuple::vector(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            p.local_normal_at(Tuple::point(10.0, 0.0, -10.0), &hit),
+            Tuple::vector(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            p.local_normal_at(Tuple::point(-5.0, 0.0, 150.0), &hit),
+            Tuple::vector(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_intersect_with_a_ray_parallel_to_the_plane() {
+        let p = plane();
+        let r = ray(Tuple::point(0.0, 10.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(p.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn test_intersect_with_a_coplanar_ray() {
+        let p = plane();
+        let r = ray(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(p.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn test_a_ray_intersecting_a_plane_from_above() {
+        let p = plane();
+        let r = ray(Tuple::point(0.0, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+        let xs = p.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 1.0);
+        assert_eq!(xs[0].object, &p as &dyn Shape);
+    }
+
+    #[test]
+    fn test_a_ray_intersecting_a_plane_from_below() {
+        let p = plane();
+        let r = ray(Tuple::point(0.0, -1.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        let xs = p.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 1.0);
+        assert_eq!(xs[0].object, &p as &dyn Shape);
+    }
+
+    #[test]
+    fn test_a_transformed_plane_as_a_wall() {
+        let mut p = plane();
+        // stand the floor up and push it back along z
+        p.transform = translation(0.0, 0.0, 5.0) * rotation_x(std::f64::consts::FRAC_PI_2);
+        let r = ray(Tuple::point(0.0, 1.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = p.intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_relative_eq!(xs[0].t, 5.0);
+        assert_relative_eq!(
+            p.normal_at(Tuple::point(0.0, 1.0, 5.0), &intersection(0.0, &p)),
+            Tuple::vector(0.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_creating_a_stripe_pattern() {
+        let white = color(1.0, 1.0, 1.0);
+        let black = color(0.0, 0.0, 0.0);
+        let pattern = stripe_pattern(white, black);
+        assert_eq!(pattern.kind, PatternKind::Stripe(white, black));
+        assert_eq!(pattern.transform, Matrix4::identity());
+    }
+
+    #[test]
+    fn test_a_stripe_pattern_is_constant_in_y_and_z() {
+        let white = color(1.0, 1.0, 1.0);
+        let black = color(0.0, 0.0, 0.0);
+        let pattern = stripe_pattern(white, black);
+        for p in [
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(0.0, 2.0, 0.0),
+            Tuple::point(0.0, 0.0, 1.0),
+            Tuple::point(0.0, 0.0, 2.0),
+        ]
+        .iter()
+        {
+            assert_relative_eq!(pattern.pattern_at(*p), white);
+        }
+    }
+
+    #[test]
+    fn test_a_stripe_pattern_alternates_in_x() {
+        let white = color(1.0, 1.0, 1.0);
+        let black = color(0.0, 0.0, 0.0);
+        let pattern = stripe_pattern(white, black);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 0.0, 0.0)), white);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.9, 0.0, 0.0)), white);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(1.0, 0.0, 0.0)), black);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(-0.1, 0.0, 0.0)), black);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(-1.0, 0.0, 0.0)), black);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(-1.1, 0.0, 0.0)), white);
+    }
+
+    #[test]
+    fn test_lighting_with_a_pattern_applied() {
+        let mut m = material();
+        m.pattern = Some(stripe_pattern(color(1.0, 1.0, 1.0), color(0.0, 0.0, 0.0)));
+        m.ambient = 1.0;
+        m.diffuse = 0.0;
+        m.specular = 0.0;
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = point_light(Tuple::point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0));
+        let s = sphere();
+        let c1 = lighting(
+            &m,
+            &s,
+            &light,
+            Tuple::point(0.9, 0.0, 0.0),
+            eyev,
+            normalv,
+            false,
+        );
+        let c2 = lighting(
+            &m,
+            &s,
+            &light,
+            Tuple::point(1.1, 0.0, 0.0),
+            eyev,
+            normalv,
+            false,
+        );
+        assert_relative_eq!(c1, color(1.0, 1.0, 1.0));
+        assert_relative_eq!(c2, color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_stripes_with_an_object_transformation() {
+        let mut object = sphere();
+        object.transform = scaling(2.0, 2.0, 2.0);
+        let pattern = stripe_pattern(color(1.0, 1.0, 1.0), color(0.0, 0.0, 0.0));
+        let c = pattern.pattern_at_shape(&object, Tuple::point(1.5, 0.0, 0.0));
+        assert_relative_eq!(c, color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_stripes_with_a_pattern_transformation() {
+        let object = sphere();
+        let mut pattern = stripe_pattern(color(1.0, 1.0, 1.0), color(0.0, 0.0, 0.0));
+        pattern.transform = scaling(2.0, 2.0, 2.0);
+        let c = pattern.pattern_at_shape(&object, Tuple::point(1.5, 0.0, 0.0));
+        assert_relative_eq!(c, color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_stripes_with_both_an_object_and_a_pattern_transformation() {
+        let mut object = sphere();
+        object.transform = scaling(2.0, 2.0, 2.0);
+        let mut pattern = stripe_pattern(color(1.0, 1.0, 1.0), color(0.0, 0.0, 0.0));
+        pattern.transform = translation(0.5, 0.0, 0.0);
+        let c = pattern.pattern_at_shape(&object, Tuple::point(2.5, 0.0, 0.0));
+        assert_relative_eq!(c, color(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_a_gradient_linearly_interpolates_between_colors() {
+        let white = color(1.0, 1.0, 1.0);
+        let black = color(0.0, 0.0, 0.0);
+        let pattern = gradient_pattern(white, black);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 0.0, 0.0)), white);
+        assert_relative_eq!(
+            pattern.pattern_at(Tuple::point(0.25, 0.0, 0.0)),
+            color(0.75, 0.75, 0.75)
+        );
+        assert_relative_eq!(
+            pattern.pattern_at(Tuple::point(0.5, 0.0, 0.0)),
+            color(0.5, 0.5, 0.5)
+        );
+        assert_relative_eq!(
+            pattern.pattern_at(Tuple::point(0.75, 0.0, 0.0)),
+            color(0.25, 0.25, 0.25)
+        );
+    }
+
+    #[test]
+    fn test_a_ring_should_extend_in_both_x_and_z() {
+        let white = color(1.0, 1.0, 1.0);
+        let black = color(0.0, 0.0, 0.0);
+        let pattern = ring_pattern(white, black);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 0.0, 0.0)), white);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(1.0, 0.0, 0.0)), black);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 0.0, 1.0)), black);
+        // 0.708 = just slightly more than sqrt(2)/2
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.708, 0.0, 0.708)), black);
+    }
+
+    #[test]
+    fn test_checkers_should_repeat_in_x() {
+        let white = color(1.0, 1.0, 1.0);
+        let black = color(0.0, 0.0, 0.0);
+        let pattern = checkers_pattern(white, black);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 0.0, 0.0)), white);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.99, 0.0, 0.0)), white);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(1.01, 0.0, 0.0)), black);
+    }
+
+    #[test]
+    fn test_checkers_should_repeat_in_y() {
+        let white = color(1.0, 1.0, 1.0);
+        let black = color(0.0, 0.0, 0.0);
+        let pattern = checkers_pattern(white, black);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 0.0, 0.0)), white);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 0.99, 0.0)), white);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 1.01, 0.0)), black);
+    }
+
+    #[test]
+    fn test_checkers_should_repeat_in_z() {
+        let white = color(1.0, 1.0, 1.0);
+        let black = color(0.0, 0.0, 0.0);
+        let pattern = checkers_pattern(white, black);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 0.0, 0.0)), white);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 0.0, 0.99)), white);
+        assert_relative_eq!(pattern.pattern_at(Tuple::point(0.0, 0.0, 1.01)), black);
+    }
+
+    #[test]
+    fn test_reflectivity_for_the_default_material() {
+        let m = material();
+        assert_eq!(m.reflective, 0.0);
+    }
+
+    #[test]
+    fn test_precomputing_the_reflection_vector() {
+        let shape = plane();
+        let k = 2.0_f64.sqrt() / 2.0;
+        let r = ray(Tuple::point(0.0, 1.0, -1.0), Tuple::vector(0.0, -k, k));
+        let i = intersection(2.0_f64.sqrt(), &shape);
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_relative_eq!(comps.reflectv, Tuple::vector(0.0, k, k));
+    }
+
+    #[test]
+    fn test_the_reflected_color_for_a_nonreflective_material() {
+        let mut w = default_world();
+        let outer = sphere();
+        let mut inner = sphere();
+        inner.transform = scaling(0.5, 0.5, 0.5);
+        inner.material.ambient = 1.0;
+        w.objects = vec![Box::new(outer), Box::new(inner)];
+        let r = ray(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let i = intersection(1.0, w.objects[1].as_ref());
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_relative_eq!(
+            reflected_color(&w, &comps, DEFAULT_MAX_DEPTH),
+            color(0.0, 0.0, 0.0)
+        );
+    }
+
+    // the default world with a half-reflective floor beneath it
+    fn world_with_reflective_floor() -> World {
+        let mut w = default_world();
+        let mut shape = plane();
+        shape.material.reflective = 0.5;
+        shape.transform = translation(0.0, -1.0, 0.0);
+        w.objects.push(Box::new(shape));
+        w
+    }
+
+    #[test]
+    fn test_the_reflected_color_for_a_reflective_material() {
+        let w = world_with_reflective_floor();
+        let k = 2.0_f64.sqrt() / 2.0;
+        let r = ray(Tuple::point(0.0, 0.0, -3.0), Tuple::vector(0.0, -k, k));
+        let i = intersection(2.0_f64.sqrt(), w.objects[2].as_ref());
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_relative_eq!(
+            reflected_color(&w, &comps, DEFAULT_MAX_DEPTH),
+            color(0.19032, 0.2379, 0.14274)
+        );
+    }
+
+    #[test]
+    fn test_shade_hit_with_a_reflective_material() {
+        let w = world_with_reflective_floor();
+        let k = 2.0_f64.sqrt() / 2.0;
+        let r = ray(Tuple::point(0.0, 0.0, -3.0), Tuple::vector(0.0, -k, k));
+        let i = intersection(2.0_f64.sqrt(), w.objects[2].as_ref());
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_relative_eq!(
+            shade_hit(&w, &comps, DEFAULT_MAX_DEPTH),
+            color(0.87677, 0.92436, 0.82918)
+        );
+    }
+
+    #[test]
+    fn test_color_at_with_mutually_reflective_surfaces() {
+        let mut w = world();
+        w.lights = vec![point_light(
+            Tuple::point(0.0, 0.0, 0.0),
+            color(1.0, 1.0, 1.0),
+        )];
+        let mut lower = plane();
+        lower.material.reflective = 1.0;
+        lower.transform = translation(0.0, -1.0, 0.0);
+        let mut upper = plane();
+        upper.material.reflective = 1.0;
+        upper.transform = translation(0.0, 1.0, 0.0);
+        w.objects = vec![Box::new(lower), Box::new(upper)];
+        let r = ray(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        // terminates rather than overflowing the stack
+        color_at(&w, &r, DEFAULT_MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_the_reflected_color_at_the_maximum_recursive_depth() {
+        let w = world_with_reflective_floor();
+        let k = 2.0_f64.sqrt() / 2.0;
+        let r = ray(Tuple::point(0.0, 0.0, -3.0), Tuple::vector(0.0, -k, k));
+        let i = intersection(2.0_f64.sqrt(), w.objects[2].as_ref());
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_relative_eq!(reflected_color(&w, &comps, 0), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_the_camera_recursion_limit_is_configurable() {
+        let mut c = camera(11, 11, std::f64::consts::FRAC_PI_2);
+        assert_eq!(c.max_depth, DEFAULT_MAX_DEPTH);
+        c.max_depth = 0;
+        let w = world_with_reflective_floor();
+        c.transform = view_transform(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::point(0.0, -1.0, -2.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        // with no bounces left the floor shows only its own surface color
+        let image = c.render(&w);
+        let r = c.ray_for_pixel(5, 5);
+        let xs = intersect_world(&w, &r);
+        let comps = prepare_computations(xs.hit().unwrap(), &r, &xs);
+        assert_relative_eq!(pixel_at(&image, 5, 5), shade_hit(&w, &comps, 0));
+    }
+
+    fn glass_sphere() -> Sphere {
+        let mut s = sphere();
+        s.material.transparency = 1.0;
+        s.material.refractive_index = 1.5;
+        s
+    }
+
+    #[test]
+    fn test_transparency_and_refractive_index_for_the_default_material() {
+        let m = material();
+        assert_eq!(m.transparency, 0.0);
+        assert_eq!(m.refractive_index, 1.0);
     }
 
-    // subtracting two vectors
     #[test]
-    fn test_sub_vecs() {
-        let v1 = Tuple::vector(3.0, 2.0, 1.0);
-        let v2 = Tuple::vector(5.0, 6.0, 7.0);
-        assert_eq!(v1 - v2, Tuple::vector(-2.0, -4.0, -6.0));
+    fn test_a_helper_for_producing_a_sphere_with_a_glassy_material() {
+        let s = glass_sphere();
+        assert_eq!(s.transform, Matrix4::identity());
+        assert_eq!(s.material.transparency, 1.0);
+        assert_eq!(s.material.refractive_index, 1.5);
+    }
+
+    #[test]
+    fn test_finding_n1_and_n2_at_various_intersections() {
+        let mut a = glass_sphere();
+        a.transform = scaling(2.0, 2.0, 2.0);
+        a.material.refractive_index = 1.5;
+        let mut b = glass_sphere();
+        b.transform = translation(0.0, 0.0, -0.25);
+        b.material.refractive_index = 2.0;
+        let mut c = glass_sphere();
+        c.transform = translation(0.0, 0.0, 0.25);
+        c.material.refractive_index = 2.5;
+        let r = ray(Tuple::point(0.0, 0.0, -4.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = intersections(vec![
+            intersection(2.0, &a),
+            intersection(2.75, &b),
+            intersection(3.25, &c),
+            intersection(4.75, &b),
+            intersection(5.25, &c),
+            intersection(6.0, &a),
+        ]);
+        let want = [
+            (1.0, 1.5),
+            (1.5, 2.0),
+            (2.0, 2.5),
+            (2.5, 2.5),
+            (2.5, 1.5),
+            (1.5, 1.0),
+        ];
+        for (index, (n1, n2)) in want.iter().enumerate() {
+            let comps = prepare_computations(&xs[index], &r, &xs);
+            assert_eq!(comps.n1, *n1);
+            assert_eq!(comps.n2, *n2);
+        }
+    }
+
+    #[test]
+    fn test_the_under_point_is_offset_below_the_surface() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut shape = glass_sphere();
+        shape.transform = translation(0.0, 0.0, 1.0);
+        let i = intersection(5.0, &shape);
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert!(comps.under_point.z > ERR / 2.0);
+        assert!(comps.point.z < comps.under_point.z);
+    }
+
+    #[test]
+    fn test_the_refracted_color_with_an_opaque_surface() {
+        let w = default_world();
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let shape = w.objects[0].as_ref();
+        let xs = [intersection(4.0, shape), intersection(6.0, shape)];
+        let comps = prepare_computations(&xs[0], &r, &xs);
+        assert_relative_eq!(refracted_color(&w, &comps, 5), color(0.0, 0.0, 0.0));
+    }
+
+    // the default world with a glassy outer sphere
+    fn world_with_glass_outer_sphere() -> World {
+        let mut w = default_world();
+        let mut outer = sphere();
+        outer.material.color = color(0.8, 1.0, 0.6);
+        outer.material.diffuse = 0.7;
+        outer.material.specular = 0.2;
+        outer.material.transparency = 1.0;
+        outer.material.refractive_index = 1.5;
+        w.objects[0] = Box::new(outer);
+        w
+    }
+
+    #[test]
+    fn test_the_refracted_color_at_the_maximum_recursive_depth() {
+        let w = world_with_glass_outer_sphere();
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let shape = w.objects[0].as_ref();
+        let xs = [intersection(4.0, shape), intersection(6.0, shape)];
+        let comps = prepare_computations(&xs[0], &r, &xs);
+        assert_relative_eq!(refracted_color(&w, &comps, 0), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_the_refracted_color_under_total_internal_reflection() {
+        let w = world_with_glass_outer_sphere();
+        let k = 2.0_f64.sqrt() / 2.0;
+        let r = ray(Tuple::point(0.0, 0.0, k), Tuple::vector(0.0, 1.0, 0.0));
+        let shape = w.objects[0].as_ref();
+        let xs = [intersection(-k, shape), intersection(k, shape)];
+        // inside the sphere, so the hit is the second intersection
+        let comps = prepare_computations(&xs[1], &r, &xs);
+        assert_relative_eq!(refracted_color(&w, &comps, 5), color(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_shade_hit_with_a_transparent_material() {
+        let mut w = default_world();
+        let mut floor = plane();
+        floor.transform = translation(0.0, -1.0, 0.0);
+        floor.material.transparency = 0.5;
+        floor.material.refractive_index = 1.5;
+        let mut ball = sphere();
+        ball.material.color = color(1.0, 0.0, 0.0);
+        ball.material.ambient = 0.5;
+        ball.transform = translation(0.0, -3.5, -0.5);
+        w.objects.push(Box::new(floor));
+        w.objects.push(Box::new(ball));
+        let k = 2.0_f64.sqrt() / 2.0;
+        let r = ray(Tuple::point(0.0, 0.0, -3.0), Tuple::vector(0.0, -k, k));
+        let xs = [intersection(2.0_f64.sqrt(), w.objects[2].as_ref())];
+        let comps = prepare_computations(&xs[0], &r, &xs);
+        assert_relative_eq!(shade_hit(&w, &comps, 5), color(0.93642, 0.68642, 0.68642));
+    }
+
+    #[test]
+    fn test_the_schlick_approximation_under_total_internal_reflection() {
+        let shape = glass_sphere();
+        let k = 2.0_f64.sqrt() / 2.0;
+        let r = ray(Tuple::point(0.0, 0.0, k), Tuple::vector(0.0, 1.0, 0.0));
+        let xs = [intersection(-k, &shape), intersection(k, &shape)];
+        let comps = prepare_computations(&xs[1], &r, &xs);
+        assert_eq!(schlick(&comps), 1.0);
+    }
+
+    #[test]
+    fn test_the_schlick_approximation_with_a_perpendicular_viewing_angle() {
+        let shape = glass_sphere();
+        let r = ray(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        let xs = [intersection(-1.0, &shape), intersection(1.0, &shape)];
+        let comps = prepare_computations(&xs[1], &r, &xs);
+        assert_relative_eq!(schlick(&comps), 0.04, epsilon = ERR);
+    }
+
+    #[test]
+    fn test_the_schlick_approximation_with_small_angle_and_n2_greater_than_n1() {
+        let shape = glass_sphere();
+        let r = ray(Tuple::point(0.0, 0.99, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = [intersection(1.8589, &shape)];
+        let comps = prepare_computations(&xs[0], &r, &xs);
+        assert_relative_eq!(schlick(&comps), 0.48873, epsilon = ERR);
+    }
+
+    #[test]
+    fn test_shade_hit_with_a_reflective_transparent_material() {
+        let mut w = default_world();
+        let mut floor = plane();
+        floor.transform = translation(0.0, -1.0, 0.0);
+        floor.material.reflective = 0.5;
+        floor.material.transparency = 0.5;
+        floor.material.refractive_index = 1.5;
+        let mut ball = sphere();
+        ball.material.color = color(1.0, 0.0, 0.0);
+        ball.material.ambient = 0.5;
+        ball.transform = translation(0.0, -3.5, -0.5);
+        w.objects.push(Box::new(floor));
+        w.objects.push(Box::new(ball));
+        let k = 2.0_f64.sqrt() / 2.0;
+        let r = ray(Tuple::point(0.0, 0.0, -3.0), Tuple::vector(0.0, -k, k));
+        let xs = [intersection(2.0_f64.sqrt(), w.objects[2].as_ref())];
+        let comps = prepare_computations(&xs[0], &r, &xs);
+        assert_relative_eq!(shade_hit(&w, &comps, 5), color(0.93391, 0.69643, 0.69243));
+    }
+
+    #[test]
+    fn test_a_ray_intersects_a_cube() {
+        let c = cube();
+        let cases = [
+            // +x, -x, +y, -y, +z, -z, inside
+            (
+                Tuple::point(5.0, 0.5, 0.0),
+                Tuple::vector(-1.0, 0.0, 0.0),
+                4.0,
+                6.0,
+            ),
+            (
+                Tuple::point(-5.0, 0.5, 0.0),
+                Tuple::vector(1.0, 0.0, 0.0),
+                4.0,
+                6.0,
+            ),
+            (
+                Tuple::point(0.5, 5.0, 0.0),
+                Tuple::vector(0.0, -1.0, 0.0),
+                4.0,
+                6.0,
+            ),
+            (
+                Tuple::point(0.5, -5.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+                4.0,
+                6.0,
+            ),
+            (
+                Tuple::point(0.5, 0.0, 5.0),
+                Tuple::vector(0.0, 0.0, -1.0),
+                4.0,
+                6.0,
+            ),
+            (
+                Tuple::point(0.5, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                4.0,
+                6.0,
+            ),
+            (
+                Tuple::point(0.0, 0.5, 0.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                -1.0,
+                1.0,
+            ),
+        ];
+        for (origin, direction, t1, t2) in cases.iter() {
+            let xs = c.local_intersect(&ray(*origin, *direction));
+            assert_eq!(xs.len(), 2);
+            assert_eq!(xs[0].t, *t1);
+            assert_eq!(xs[1].t, *t2);
+        }
+    }
+
+    #[test]
+    fn test_a_ray_misses_a_cube() {
+        let c = cube();
+        let cases = [
+            (
+                Tuple::point(-2.0, 0.0, 0.0),
+                Tuple::vector(0.2673, 0.5345, 0.8018),
+            ),
+            (
+                Tuple::point(0.0, -2.0, 0.0),
+                Tuple::vector(0.8018, 0.2673, 0.5345),
+            ),
+            (
+                Tuple::point(0.0, 0.0, -2.0),
+                Tuple::vector(0.5345, 0.8018, 0.2673),
+            ),
+            (Tuple::point(2.0, 0.0, 2.0), Tuple::vector(0.0, 0.0, -1.0)),
+            (Tuple::point(0.0, 2.0, 2.0), Tuple::vector(0.0, -1.0, 0.0)),
+            (Tuple::point(2.0, 2.0, 0.0), Tuple::vector(-1.0, 0.0, 0.0)),
+        ];
+        for (origin, direction) in cases.iter() {
+            assert!(c.local_intersect(&ray(*origin, *direction)).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_the_normal_on_the_surface_of_a_cube() {
+        let c = cube();
+        let cases = [
+            (Tuple::point(1.0, 0.5, -0.8), Tuple::vector(1.0, 0.0, 0.0)),
+            (Tuple::point(-1.0, -0.2, 0.9), Tuple::vector(-1.0, 0.0, 0.0)),
+            (Tuple::point(-0.4, 1.0, -0.1), Tuple::vector(0.0, 1.0, 0.0)),
+            (Tuple::point(0.3, -1.0, -0.7), Tuple::vector(0.0, -1.0, 0.0)),
+            (Tuple::point(-0.6, 0.3, 1.0), Tuple::vector(0.0, 0.0, 1.0)),
+            (Tuple::point(0.4, 0.4, -1.0), Tuple::vector(0.0, 0.0, -1.0)),
+            // corners resolve to the x faces
+            (Tuple::point(1.0, 1.0, 1.0), Tuple::vector(1.0, 0.0, 0.0)),
+            (
+                Tuple::point(-1.0, -1.0, -1.0),
+                Tuple::vector(-1.0, 0.0, 0.0),
+            ),
+        ];
+        let hit = intersection(0.0, &c);
+        for (p, n) in cases.iter() {
+            assert_eq!(c.local_normal_at(*p, &hit), *n);
+        }
+    }
+
+    #[test]
+    fn test_a_ray_intersects_an_arbitrary_box() {
+        let min = Tuple::point(-1.0, 0.0, 2.0);
+        let max = Tuple::point(1.0, 4.0, 3.0);
+        let r = ray(Tuple::point(0.0, 2.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(box_intersect(&r, min, max), Some((2.0, 3.0)));
+        let r = ray(Tuple::point(0.0, 5.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(box_intersect(&r, min, max), None);
+    }
+
+    #[test]
+    fn test_a_ray_misses_a_cylinder() {
+        let cyl = cylinder();
+        let cases = [
+            (Tuple::point(1.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+            (Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+            (Tuple::point(0.0, 0.0, -5.0), Tuple::vector(1.0, 1.0, 1.0)),
+        ];
+        for (origin, direction) in cases.iter() {
+            let r = ray(*origin, normalize(*direction));
+            assert!(cyl.local_intersect(&r).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_a_ray_strikes_a_cylinder() {
+        let cyl = cylinder();
+        let cases = [
+            (
+                Tuple::point(1.0, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                5.0,
+                5.0,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                4.0,
+                6.0,
+            ),
+            (
+                Tuple::point(0.5, 0.0, -5.0),
+                Tuple::vector(0.1, 1.0, 1.0),
+                6.80798,
+                7.08872,
+            ),
+        ];
+        for (origin, direction, t0, t1) in cases.iter() {
+            let r = ray(*origin, normalize(*direction));
+            let xs = cyl.local_intersect(&r);
+            assert_eq!(xs.len(), 2);
+            assert_relative_eq!(xs[0].t, *t0, epsilon = ERR);
+            assert_relative_eq!(xs[1].t, *t1, epsilon = ERR);
+        }
+    }
+
+    #[test]
+    fn test_the_normal_vector_on_a_cylinder() {
+        let cyl = cylinder();
+        let cases = [
+            (Tuple::point(1.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0)),
+            (Tuple::point(0.0, 5.0, -1.0), Tuple::vector(0.0, 0.0, -1.0)),
+            (Tuple::point(0.0, -2.0, 1.0), Tuple::vector(0.0, 0.0, 1.0)),
+            (Tuple::point(-1.0, 1.0, 0.0), Tuple::vector(-1.0, 0.0, 0.0)),
+        ];
+        let hit = intersection(0.0, &cyl);
+        for (p, n) in cases.iter() {
+            assert_eq!(cyl.local_normal_at(*p, &hit), *n);
+        }
+    }
+
+    #[test]
+    fn test_the_default_minimum_and_maximum_for_a_cylinder() {
+        let cyl = cylinder();
+        assert_eq!(cyl.minimum, -f64::INFINITY);
+        assert_eq!(cyl.maximum, f64::INFINITY);
+        assert!(!cyl.closed);
+    }
+
+    #[test]
+    fn test_intersecting_a_constrained_cylinder() {
+        let mut cyl = cylinder();
+        cyl.minimum = 1.0;
+        cyl.maximum = 2.0;
+        let cases = [
+            (Tuple::point(0.0, 1.5, 0.0), Tuple::vector(0.1, 1.0, 0.0), 0),
+            (
+                Tuple::point(0.0, 3.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                0,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                0,
+            ),
+            (
+                Tuple::point(0.0, 2.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                0,
+            ),
+            (
+                Tuple::point(0.0, 1.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                0,
+            ),
+            (
+                Tuple::point(0.0, 1.5, -2.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                2,
+            ),
+        ];
+        for (origin, direction, count) in cases.iter() {
+            let r = ray(*origin, normalize(*direction));
+            assert_eq!(cyl.local_intersect(&r).len(), *count);
+        }
+    }
+
+    #[test]
+    fn test_intersecting_the_caps_of_a_closed_cylinder() {
+        let mut cyl = cylinder();
+        cyl.minimum = 1.0;
+        cyl.maximum = 2.0;
+        cyl.closed = true;
+        let cases = [
+            (
+                Tuple::point(0.0, 3.0, 0.0),
+                Tuple::vector(0.0, -1.0, 0.0),
+                2,
+            ),
+            (
+                Tuple::point(0.0, 3.0, -2.0),
+                Tuple::vector(0.0, -1.0, 2.0),
+                2,
+            ),
+            // corner case
+            (
+                Tuple::point(0.0, 4.0, -2.0),
+                Tuple::vector(0.0, -1.0, 1.0),
+                2,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -2.0),
+                Tuple::vector(0.0, 1.0, 2.0),
+                2,
+            ),
+            // corner case
+            (
+                Tuple::point(0.0, -1.0, -2.0),
+                Tuple::vector(0.0, 1.0, 1.0),
+                2,
+            ),
+        ];
+        for (origin, direction, count) in cases.iter() {
+            let r = ray(*origin, normalize(*direction));
+            assert_eq!(cyl.local_intersect(&r).len(), *count);
+        }
+    }
+
+    #[test]
+    fn test_the_normal_vector_on_a_cylinders_end_caps() {
+        let mut cyl = cylinder();
+        cyl.minimum = 1.0;
+        cyl.maximum = 2.0;
+        cyl.closed = true;
+        let cases = [
+            (Tuple::point(0.0, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0)),
+            (Tuple::point(0.5, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0)),
+            (Tuple::point(0.0, 1.0, 0.5), Tuple::vector(0.0, -1.0, 0.0)),
+            (Tuple::point(0.0, 2.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+            (Tuple::point(0.5, 2.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+            (Tuple::point(0.0, 2.0, 0.5), Tuple::vector(0.0, 1.0, 0.0)),
+        ];
+        let hit = intersection(0.0, &cyl);
+        for (p, n) in cases.iter() {
+            assert_eq!(cyl.local_normal_at(*p, &hit), *n);
+        }
+    }
+
+    #[test]
+    fn test_intersecting_a_cone_with_a_ray() {
+        let shape = cone();
+        let cases = [
+            (
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                5.0,
+                5.0,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::vector(1.0, 1.0, 1.0),
+                8.66025,
+                8.66025,
+            ),
+            (
+                Tuple::point(1.0, 1.0, -5.0),
+                Tuple::vector(-0.5, -1.0, 1.0),
+                4.55006,
+                49.44994,
+            ),
+        ];
+        for (origin, direction, t0, t1) in cases.iter() {
+            let r = ray(*origin, normalize(*direction));
+            let xs = shape.local_intersect(&r);
+            assert_eq!(xs.len(), 2);
+            assert_relative_eq!(xs[0].t, *t0, epsilon = ERR);
+            assert_relative_eq!(xs[1].t, *t1, epsilon = ERR);
+        }
+    }
+
+    #[test]
+    fn test_intersecting_a_cone_with_a_ray_parallel_to_one_of_its_halves() {
+        let shape = cone();
+        let r = ray(
+            Tuple::point(0.0, 0.0, -1.0),
+            normalize(Tuple::vector(0.0, 1.0, 1.0)),
+        );
+        let xs = shape.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_relative_eq!(xs[0].t, 0.35355, epsilon = ERR);
+    }
+
+    #[test]
+    fn test_intersecting_a_cones_end_caps() {
+        let mut shape = cone();
+        shape.minimum = -0.5;
+        shape.maximum = 0.5;
+        shape.closed = true;
+        let cases = [
+            (
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+                0,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -0.25),
+                Tuple::vector(0.0, 1.0, 1.0),
+                2,
+            ),
+            (
+                Tuple::point(0.0, 0.0, -0.25),
+                Tuple::vector(0.0, 1.0, 0.0),
+                4,
+            ),
+        ];
+        for (origin, direction, count) in cases.iter() {
+            let r = ray(*origin, normalize(*direction));
+            assert_eq!(shape.local_intersect(&r).len(), *count);
+        }
+    }
+
+    #[test]
+    fn test_computing_the_normal_vector_on_a_cone() {
+        let shape = cone();
+        let cases = [
+            (Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 0.0)),
+            (
+                Tuple::point(1.0, 1.0, 1.0),
+                Tuple::vector(1.0, -(2.0_f64.sqrt()), 1.0),
+            ),
+            (Tuple::point(-1.0, -1.0, 0.0), Tuple::vector(-1.0, 1.0, 0.0)),
+        ];
+        let hit = intersection(0.0, &shape);
+        for (p, n) in cases.iter() {
+            assert_relative_eq!(shape.local_normal_at(*p, &hit), *n);
+        }
+    }
+
+    #[test]
+    fn test_the_normal_vector_on_a_cones_end_caps() {
+        let mut shape = cone();
+        shape.minimum = -1.0;
+        shape.maximum = 2.0;
+        shape.closed = true;
+        let hit = intersection(0.0, &shape);
+        assert_eq!(
+            shape.local_normal_at(Tuple::point(0.5, 2.0, 0.0), &hit),
+            Tuple::vector(0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            shape.local_normal_at(Tuple::point(0.5, -1.0, 0.0), &hit),
+            Tuple::vector(0.0, -1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_creating_a_new_group() {
+        let g = group();
+        assert_eq!(g.transform(), &Matrix4::identity());
+        assert!(g.children().is_empty());
+    }
+
+    #[test]
+    fn test_a_shape_has_no_parent_transform_by_default() {
+        let s = test_shape();
+        assert_eq!(s.parent_transform(), &Matrix4::identity());
+    }
+
+    #[test]
+    fn test_adding_a_child_to_a_group() {
+        let mut g = group();
+        g.set_transform(translation(1.0, 2.0, 3.0));
+        g.add_child(Box::new(test_shape()));
+        assert_eq!(g.children().len(), 1);
+        assert_eq!(
+            g.children()[0].parent_transform(),
+            &translation(1.0, 2.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn test_intersecting_a_ray_with_an_empty_group() {
+        let g = group();
+        let r = ray(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(g.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn test_intersecting_a_ray_with_a_nonempty_group() {
+        let mut g = group();
+        let s1 = sphere();
+        let mut s2 = sphere();
+        s2.transform = translation(0.0, 0.0, -3.0);
+        let mut s3 = sphere();
+        s3.transform = translation(5.0, 0.0, 0.0);
+        g.add_child(Box::new(s1));
+        g.add_child(Box::new(s2));
+        g.add_child(Box::new(s3));
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = intersections(g.local_intersect(&r));
+        let s1 = g.children()[0].as_ref();
+        let s2 = g.children()[1].as_ref();
+        assert_eq!(xs.len(), 4);
+        assert_eq!(xs[0].object, s2);
+        assert_eq!(xs[1].object, s2);
+        assert_eq!(xs[2].object, s1);
+        assert_eq!(xs[3].object, s1);
+    }
+
+    #[test]
+    fn test_intersecting_a_transformed_group() {
+        let mut g = group();
+        g.set_transform(scaling(2.0, 2.0, 2.0));
+        let mut s = sphere();
+        s.transform = translation(5.0, 0.0, 0.0);
+        g.add_child(Box::new(s));
+        let r = ray(Tuple::point(10.0, 0.0, -10.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(g.intersect(&r).len(), 2);
+    }
+
+    // a sphere nested two groups deep, the inner group scaled by `inner`,
+    // and a ray from the origin that hits it
+    fn nested_sphere_group(inner: Matrix4<f64>) -> (Group, Ray) {
+        let mut s = sphere();
+        s.transform = translation(5.0, 0.0, 0.0);
+        let mut g2 = group();
+        g2.set_transform(inner);
+        g2.add_child(Box::new(s));
+        let mut g1 = group();
+        g1.set_transform(rotation_y(std::f64::consts::FRAC_PI_2));
+        g1.add_child(Box::new(g2));
+        let r = ray(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, -1.0));
+        (g1, r)
+    }
+
+    #[test]
+    fn test_converting_a_point_from_world_to_object_space() {
+        let (g1, r) = nested_sphere_group(scaling(2.0, 2.0, 2.0));
+        let xs = g1.intersect(&r);
+        let s = xs[0].object;
+        assert_relative_eq!(
+            s.world_to_object(Tuple::point(-2.0, 0.0, -10.0)),
+            Tuple::point(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn test_converting_a_normal_from_object_to_world_space() {
+        let (g1, r) = nested_sphere_group(scaling(1.0, 2.0, 3.0));
+        let xs = g1.intersect(&r);
+        let s = xs[0].object;
+        let k = 3.0_f64.sqrt() / 3.0;
+        let n = s.normal_to_world(Tuple::vector(k, k, k));
+        assert_relative_eq!(n, Tuple::vector(0.2857, 0.4286, -0.8571));
+    }
+
+    #[test]
+    fn test_finding_the_normal_on_a_child_object() {
+        let (g1, r) = nested_sphere_group(scaling(1.0, 2.0, 3.0));
+        let xs = g1.intersect(&r);
+        let s = xs[0].object;
+        let n = s.normal_at(Tuple::point(1.7321, 1.1547, -5.5774), &xs[0]);
+        assert_relative_eq!(n, Tuple::vector(0.2857, 0.4286, -0.8571));
+    }
+
+    #[test]
+    fn test_transforming_a_group_updates_its_descendants() {
+        let (mut g1, _) = nested_sphere_group(scaling(2.0, 2.0, 2.0));
+        g1.set_transform(translation(0.0, 0.0, 100.0));
+        let r = ray(Tuple::point(10.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = g1.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        // the sphere now sits at (10, 0, 100) with radius 2
+        assert_relative_eq!(
+            xs[0].object.world_to_object(Tuple::point(10.0, 0.0, 98.0)),
+            Tuple::point(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn test_constructing_a_triangle() {
+        let p1 = Tuple::point(0.0, 1.0, 0.0);
+        let p2 = Tuple::point(-1.0, 0.0, 0.0);
+        let p3 = Tuple::point(1.0, 0.0, 0.0);
+        let t = triangle(p1, p2, p3);
+        assert_eq!(t.vertices(), [p1, p2, p3]);
+        assert_eq!(t.e1, Tuple::vector(-1.0, -1.0, 0.0));
+        assert_eq!(t.e2, Tuple::vector(1.0, -1.0, 0.0));
+        assert_eq!(t.normal, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    fn default_triangle() -> Triangle {
+        triangle(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_finding_the_normal_on_a_triangle() {
+        let t = default_triangle();
+        let hit = intersection(0.0, &t);
+        assert_eq!(
+            t.local_normal_at(Tuple::point(0.0, 0.5, 0.0), &hit),
+            t.normal
+        );
+        assert_eq!(
+            t.local_normal_at(Tuple::point(-0.5, 0.75, 0.0), &hit),
+            t.normal
+        );
+        assert_eq!(
+            t.local_normal_at(Tuple::point(0.5, 0.25, 0.0), &hit),
+            t.normal
+        );
+    }
+
+    #[test]
+    fn test_intersecting_a_ray_parallel_to_the_triangle() {
+        let t = default_triangle();
+        let r = ray(Tuple::point(0.0, -1.0, -2.0), Tuple::vector(0.0, 1.0, 0.0));
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn test_a_ray_misses_the_p1_p3_edge() {
+        let t = default_triangle();
+        let r = ray(Tuple::point(1.0, 1.0, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn test_a_ray_misses_the_p1_p2_edge() {
+        let t = default_triangle();
+        let r = ray(Tuple::point(-1.0, 1.0, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn test_a_ray_misses_the_p2_p3_edge() {
+        let t = default_triangle();
+        let r = ray(Tuple::point(0.0, -1.0, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn test_a_ray_strikes_a_triangle() {
+        let t = default_triangle();
+        let r = ray(Tuple::point(0.0, 0.5, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = t.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 2.0);
+    }
+
+    fn default_smooth_triangle() -> Triangle {
+        smooth_triangle(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+            Tuple::vector(-1.0, 0.0, 0.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_an_intersection_can_encapsulate_u_and_v() {
+        let s = default_triangle();
+        let i = intersection_with_uv(3.5, &s, 0.2, 0.4);
+        assert_eq!(i.u, 0.2);
+        assert_eq!(i.v, 0.4);
+    }
+
+    #[test]
+    fn test_an_intersection_with_a_smooth_triangle_stores_u_and_v() {
+        let tri = default_smooth_triangle();
+        let r = ray(Tuple::point(-0.2, 0.3, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = tri.local_intersect(&r);
+        assert_relative_eq!(xs[0].u, 0.45, epsilon = ERR);
+        assert_relative_eq!(xs[0].v, 0.25, epsilon = ERR);
+    }
+
+    #[test]
+    fn test_a_smooth_triangle_uses_u_and_v_to_interpolate_the_normal() {
+        let tri = default_smooth_triangle();
+        let i = intersection_with_uv(1.0, &tri, 0.45, 0.25);
+        let n = tri.normal_at(Tuple::point(0.0, 0.0, 0.0), &i);
+        assert_relative_eq!(n, Tuple::vector(-0.5547, 0.83205, 0.0));
+    }
+
+    #[test]
+    fn test_preparing_the_normal_on_a_smooth_triangle() {
+        let tri = default_smooth_triangle();
+        let i = intersection_with_uv(1.0, &tri, 0.45, 0.25);
+        let r = ray(Tuple::point(-0.2, 0.3, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        let comps = prepare_computations(&i, &r, &[i]);
+        assert_relative_eq!(comps.normalv, Tuple::vector(-0.5547, 0.83205, 0.0));
+    }
+
+    #[test]
+    fn test_ignoring_unrecognized_lines() {
+        let gibberish = r#"There was a young lady named Bright
+who traveled much faster than light.
+She set out one day
+in a relative way,
+and came back the previous night.
+"#;
+        let obj = parse_obj_file(gibberish);
+        assert_eq!(obj.ignored, 5);
+        assert!(obj.vertices.is_empty());
+    }
+
+    #[test]
+    fn test_vertex_records() {
+        let file = r#"v -1 1 0
+v -1.0000 0.5000 0.0000
+v 1 0 0
+v 1 1 0
+"#;
+        let obj = parse_obj_file(file);
+        assert_eq!(obj.ignored, 0);
+        assert_eq!(
+            obj.vertices,
+            vec![
+                Tuple::point(-1.0, 1.0, 0.0),
+                Tuple::point(-1.0, 0.5, 0.0),
+                Tuple::point(1.0, 0.0, 0.0),
+                Tuple::point(1.0, 1.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parsing_triangle_faces() {
+        let file = r#"v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+f 1 2 3
+f 1 3 4
+"#;
+        let obj = parse_obj_file(file);
+        let v = &obj.vertices;
+        assert_eq!(obj.default_group.len(), 2);
+        assert_eq!(obj.default_group[0].vertices(), [v[0], v[1], v[2]]);
+        assert_eq!(obj.default_group[1].vertices(), [v[0], v[2], v[3]]);
+    }
+
+    #[test]
+    fn test_triangulating_polygons() {
+        let file = r#"v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+v 0 2 0
+
+f 1 2 3 4 5
+"#;
+        let obj = parse_obj_file(file);
+        let v = &obj.vertices;
+        assert_eq!(obj.default_group.len(), 3);
+        assert_eq!(obj.default_group[0].vertices(), [v[0], v[1], v[2]]);
+        assert_eq!(obj.default_group[1].vertices(), [v[0], v[2], v[3]]);
+        assert_eq!(obj.default_group[2].vertices(), [v[0], v[3], v[4]]);
+    }
+
+    #[test]
+    fn test_triangles_in_groups() {
+        let file = r#"v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+g FirstGroup
+f 1 2 3
+g SecondGroup
+f 1 3 4
+"#;
+        let obj = parse_obj_file(file);
+        let v = &obj.vertices;
+        let g1 = obj.group("FirstGroup").unwrap();
+        let g2 = obj.group("SecondGroup").unwrap();
+        assert_eq!(g1[0].vertices(), [v[0], v[1], v[2]]);
+        assert_eq!(g2[0].vertices(), [v[0], v[2], v[3]]);
+        assert!(obj.group("ThirdGroup").is_none());
+    }
+
+    #[test]
+    fn test_converting_an_obj_file_to_a_group() {
+        let file = r#"v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+f 1 2 3
+g FirstGroup
+f 1 2 3
+g SecondGroup
+f 1 3 4
+"#;
+        let obj = parse_obj_file(file);
+        let g = obj.obj_to_group();
+        assert_eq!(g.children().len(), 3);
+        // a ray straight through the second triangle only
+        let r = ray(Tuple::point(0.5, 0.75, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(g.intersect(&r).len(), 1);
+    }
+
+    #[test]
+    fn test_vertex_normal_records() {
+        let file = r#"vn 0 0 1
+vn 0.707 0 -0.707
+vn 1 2 3
+"#;
+        let obj = parse_obj_file(file);
+        assert_eq!(
+            obj.normals,
+            vec![
+                Tuple::vector(0.0, 0.0, 1.0),
+                Tuple::vector(0.707, 0.0, -0.707),
+                Tuple::vector(1.0, 2.0, 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_faces_with_normals() {
+        let file = r#"v 0 1 0
+v -1 0 0
+v 1 0 0
+
+vn -1 0 0
+vn 1 0 0
+vn 0 1 0
+
+f 1//3 2//1 3//2
+f 1/0/3 2/102/1 3/14/2
+"#;
+        let obj = parse_obj_file(file);
+        let v = &obj.vertices;
+        let n = &obj.normals;
+        let want = smooth_triangle(v[0], v[1], v[2], n[2], n[0], n[1]);
+        assert_eq!(obj.ignored, 0);
+        assert_eq!(obj.default_group, vec![want, want]);
+    }
+
+    #[test]
+    fn test_faces_referring_to_missing_vertices_are_skipped() {
+        let file = r#"v 0 1 0
+v -1 0 0
+v 1 0 0
+f 1 2 4
+f 1 2
+f -3 -2 -1
+v 1 x 0
+"#;
+        let obj = parse_obj_file(file);
+        assert_eq!(obj.ignored, 3);
+        assert_eq!(obj.vertices.len(), 3);
+        // negative indices count back from the last vertex
+        let v = &obj.vertices;
+        assert_eq!(obj.default_group[0].vertices(), [v[0], v[1], v[2]]);
+    }
+
+    #[test]
+    fn test_csg_is_created_with_an_operation_and_two_shapes() {
+        let mut c = csg(CsgOperation::Union, Box::new(sphere()), Box::new(cube()));
+        assert_eq!(c.operation, CsgOperation::Union);
+        c.set_transform(translation(1.0, 0.0, 0.0));
+        assert_eq!(c.left().parent_transform(), &translation(1.0, 0.0, 0.0));
+        assert_eq!(c.right().parent_transform(), &translation(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_evaluating_the_rule_for_a_csg_operation() {
+        use CsgOperation::*;
+        // op, lhit, inl, inr, result
+        let cases = [
+            (Union, true, true, true, false),
+            (Union, true, true, false, true),
+            (Union, true, false, true, false),
+            (Union, true, false, false, true),
+            (Union, false, true, true, false),
+            (Union, false, true, false, false),
+            (Union, false, false, true, true),
+            (Union, false, false, false, true),
+            (Intersection, true, true, true, true),
+            (Intersection, true, true, false, false),
+            (Intersection, true, false, true, true),
+            (Intersection, true, false, false, false),
+            (Intersection, false, true, true, true),
+            (Intersection, false, true, false, true),
+            (Intersection, false, false, true, false),
+            (Intersection, false, false, false, false),
+            (Difference, true, true, true, false),
+            (Difference, true, true, false, true),
+            (Difference, true, false, true, false),
+            (Difference, true, false, false, true),
+            (Difference, false, true, true, true),
+            (Difference, false, true, false, true),
+            (Difference, false, false, true, false),
+            (Difference, false, false, false, false),
+        ];
+        for (op, lhit, inl, inr, result) in cases.iter() {
+            assert_eq!(intersection_allowed(*op, *lhit, *inl, *inr), *result);
+        }
+    }
+
+    #[test]
+    fn test_filtering_a_list_of_intersections() {
+        let cases = [
+            (CsgOperation::Union, 0, 3),
+            (CsgOperation::Intersection, 1, 2),
+            (CsgOperation::Difference, 0, 1),
+        ];
+        for (op, x0, x1) in cases.iter() {
+            let c = csg(*op, Box::new(sphere()), Box::new(cube()));
+            let s1 = c.left();
+            let s2 = c.right();
+            let xs = intersections(vec![
+                intersection(1.0, s1),
+                intersection(2.0, s2),
+                intersection(3.0, s1),
+                intersection(4.0, s2),
+            ]);
+            let result = c.filter_intersections(&xs);
+            assert_eq!(result, vec![xs[*x0], xs[*x1]]);
+        }
     }
 
-    // subtracting a vector from the zero vector
     #[test]
-    fn test_sub_a_vec_from_zero() {
-        let z = Tuple::ZERO.clone();
-        let v = Tuple::vector(1.0, -2.0, -3.0);
-        assert_eq!(z - v, Tuple::vector(-1.0, 2.0, 3.0));
+    fn test_a_ray_misses_a_csg_object() {
+        let c = csg(CsgOperation::Union, Box::new(sphere()), Box::new(cube()));
+        let r = ray(Tuple::point(0.0, 2.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(c.local_intersect(&r).is_empty());
     }
 
-    // negating a tuple
     #[test]
-    fn test_neg() {
-        let a = Tuple::new(1.0, -2.0, 3.0, -4.0);
-        assert_eq!(-a, Tuple::new(-1.0, 2.0, -3.0, 4.0));
+    fn test_a_ray_hits_a_csg_object() {
+        let s1 = sphere();
+        let mut s2 = sphere();
+        s2.transform = translation(0.0, 0.0, 0.5);
+        let c = csg(CsgOperation::Union, Box::new(s1), Box::new(s2));
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = c.local_intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq!(xs[0].object, c.left());
+        assert_eq!(xs[1].t, 6.5);
+        assert_eq!(xs[1].object, c.right());
     }
 
-    // multiplying a tuple by a scalar
     #[test]
-    fn test_scalar_mult() {
-        let a = Tuple::new(1.0, -2.0, 3.0, -4.0);
-        assert_eq!(a * 3.5, Tuple::new(3.5, -7.0, 10.5, -14.0))
+    fn test_csg_filtering_sees_through_nested_groups() {
+        let mut g = group();
+        g.add_child(Box::new(sphere()));
+        let mut s2 = sphere();
+        s2.transform = translation(0.0, 0.0, 0.5);
+        // carve the back of the grouped sphere away
+        let c = csg(CsgOperation::Difference, Box::new(g), Box::new(s2));
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = c.local_intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq!(xs[1].t, 4.5);
+        assert!(c.left().includes(xs[0].object));
+        assert_eq!(xs[1].object, c.right());
     }
 
     #[test]
-    fn test_fraction_mult() {
-        let a = Tuple::new(1.0, -2.0, 3.0, -4.0);
-        assert_eq!(a * 0.5, Tuple::new(0.5, -1.0, 1.5, -2.0))
+    fn test_adding_points_to_an_empty_bounding_box() {
+        let mut b = empty_bounding_box();
+        b.add_point(Tuple::point(-5.0, 2.0, 0.0));
+        b.add_point(Tuple::point(7.0, 0.0, -3.0));
+        assert_eq!(b.min, Tuple::point(-5.0, 0.0, -3.0));
+        assert_eq!(b.max, Tuple::point(7.0, 2.0, 0.0));
     }
 
     #[test]
-    fn test_dividing_a_tuple_by_a_scalar() {
-        let a = Tuple::new(1.0, -2.0, 3.0, -4.0);
-        assert_eq!(a / 2.0, Tuple::new(0.5, -1.0, 1.5, -2.0))
+    fn test_shapes_have_bounding_boxes() {
+        let unit = bounding_box(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0));
+        assert_eq!(sphere().bounds(), unit);
+        assert_eq!(cube().bounds(), unit);
+        let b = plane().bounds();
+        assert_eq!(b.min, Tuple::point(-f64::INFINITY, 0.0, -f64::INFINITY));
+        assert_eq!(b.max, Tuple::point(f64::INFINITY, 0.0, f64::INFINITY));
+        let b = cylinder().bounds();
+        assert_eq!(b.min, Tuple::point(-1.0, -f64::INFINITY, -1.0));
+        assert_eq!(b.max, Tuple::point(1.0, f64::INFINITY, 1.0));
+        let mut cyl = cylinder();
+        cyl.minimum = -5.0;
+        cyl.maximum = 3.0;
+        assert_eq!(
+            cyl.bounds(),
+            bounding_box(Tuple::point(-1.0, -5.0, -1.0), Tuple::point(1.0, 3.0, 1.0))
+        );
+        let mut shape = cone();
+        shape.minimum = -5.0;
+        shape.maximum = 3.0;
+        assert_eq!(
+            shape.bounds(),
+            bounding_box(Tuple::point(-5.0, -5.0, -5.0), Tuple::point(5.0, 3.0, 5.0))
+        );
+        let t = triangle(
+            Tuple::point(-3.0, 7.0, 2.0),
+            Tuple::point(6.0, 2.0, -4.0),
+            Tuple::point(2.0, -1.0, -1.0),
+        );
+        assert_eq!(
+            t.bounds(),
+            bounding_box(Tuple::point(-3.0, -1.0, -4.0), Tuple::point(6.0, 7.0, 2.0))
+        );
     }
 
     #[test]
-    fn test_magnitude() {
-        let v = Tuple::vector(1.0, 0.0, 0.0);
-        assert_eq!(magnitude(v), 1.0)
+    fn test_adding_one_bounding_box_to_another() {
+        let mut box1 = bounding_box(Tuple::point(-5.0, -2.0, 0.0), Tuple::point(7.0, 4.0, 4.0));
+        let box2 = bounding_box(Tuple::point(8.0, -7.0, -2.0), Tuple::point(14.0, 2.0, 8.0));
+        box1.add_box(&box2);
+        assert_eq!(box1.min, Tuple::point(-5.0, -7.0, -2.0));
+        assert_eq!(box1.max, Tuple::point(14.0, 4.0, 8.0));
     }
 
     #[test]
-    fn test_magnitude2() {
-        let v = Tuple::vector(0.0, 1.0, 0.0);
-        assert_eq!(magnitude(v), 1.0)
+    fn test_checking_whether_a_box_contains_a_point_or_box() {
+        let b = bounding_box(Tuple::point(5.0, -2.0, 0.0), Tuple::point(11.0, 4.0, 7.0));
+        assert!(b.contains_point(Tuple::point(5.0, -2.0, 0.0)));
+        assert!(b.contains_point(Tuple::point(8.0, 1.0, 3.0)));
+        assert!(!b.contains_point(Tuple::point(3.0, 0.0, 3.0)));
+        assert!(!b.contains_point(Tuple::point(8.0, -4.0, 3.0)));
+        assert!(!b.contains_point(Tuple::point(11.0, 4.0, 8.0)));
+        let b = bounding_box(Tuple::point(5.0, -2.0, 0.0), Tuple::point(11.0, 4.0, 7.0));
+        let inner = bounding_box(Tuple::point(6.0, -1.0, 1.0), Tuple::point(10.0, 3.0, 6.0));
+        let straddling = bounding_box(Tuple::point(4.0, -3.0, -1.0), Tuple::point(10.0, 3.0, 6.0));
+        assert!(b.contains_box(&b));
+        assert!(b.contains_box(&inner));
+        assert!(!b.contains_box(&straddling));
     }
 
     #[test]
-    fn test_magnitude3() {
-        let v = Tuple::vector(0.0, 0.0, 1.0);
-        assert_eq!(magnitude(v), 1.0)
+    fn test_transforming_a_bounding_box() {
+        let b = bounding_box(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0));
+        let m = rotation_x(std::f64::consts::FRAC_PI_4) * rotation_y(std::f64::consts::FRAC_PI_4);
+        let b2 = b.transform(&m);
+        assert_relative_eq!(
+            b2.min,
+            Tuple::point(-std::f64::consts::SQRT_2, -1.7071, -1.7071),
+            epsilon = ERR
+        );
+        assert_relative_eq!(
+            b2.max,
+            Tuple::point(std::f64::consts::SQRT_2, 1.7071, 1.7071),
+            epsilon = ERR
+        );
     }
+
     #[test]
-    fn test_magnitude4() {
-        let v = Tuple::vector(1.0, 2.0, 3.0);
-        assert_eq!(magnitude(v), 14.0_f64.sqrt())
+    fn test_transforming_an_infinite_bounding_box() {
+        let b = plane().bounds();
+        // an untransformed plane stays flat, rather than turning to NaN
+        let b2 = b.transform(&translation(0.0, 2.0, 0.0));
+        assert_eq!(b2.min, Tuple::point(-f64::INFINITY, 2.0, -f64::INFINITY));
+        assert_eq!(b2.max, Tuple::point(f64::INFINITY, 2.0, f64::INFINITY));
+        // a tilted plane spreads out over every axis
+        let b3 = b.transform(&rotation_x(std::f64::consts::FRAC_PI_4));
+        assert_eq!(b3.min.y, -f64::INFINITY);
+        assert_eq!(b3.max.y, f64::INFINITY);
+        assert_eq!(b3.min.z, -f64::INFINITY);
+        assert_eq!(b3.max.z, f64::INFINITY);
     }
+
     #[test]
-    fn test_magnitude5() {
-        let v = Tuple::vector(-1.0, -2.0, -3.0);
-        assert_eq!(magnitude(v), 14.0_f64.sqrt())
+    fn test_querying_a_shapes_bounding_box_in_its_parents_space() {
+        let mut s = sphere();
+        s.transform = translation(1.0, -3.0, 5.0) * scaling(0.5, 2.0, 4.0);
+        let b = s.parent_space_bounds();
+        assert_relative_eq!(b.min, Tuple::point(0.5, -5.0, 1.0), epsilon = ERR);
+        assert_relative_eq!(b.max, Tuple::point(1.5, -1.0, 9.0), epsilon = ERR);
     }
+
     #[test]
-    fn test_normalizing1() {
-        let v = Tuple::vector(4.0, 0.0, 0.0);
-        assert_eq!(normalize(v), Tuple::vector(1.0, 0.0, 0.0))
+    fn test_a_group_has_a_bounding_box_that_contains_its_children() {
+        let mut s = sphere();
+        s.transform = translation(2.0, 5.0, -3.0) * scaling(2.0, 2.0, 2.0);
+        let mut c = cylinder();
+        c.minimum = -2.0;
+        c.maximum = 2.0;
+        c.transform = translation(-4.0, -1.0, 4.0) * scaling(0.5, 1.0, 0.5);
+        let mut g = group();
+        g.add_child(Box::new(s));
+        g.add_child(Box::new(c));
+        let b = g.bounds();
+        assert_relative_eq!(b.min, Tuple::point(-4.5, -3.0, -5.0), epsilon = ERR);
+        assert_relative_eq!(b.max, Tuple::point(4.0, 7.0, 4.5), epsilon = ERR);
     }
+
     #[test]
-    fn test_normalizing2() {
-        let v = Tuple::vector(1.0, 2.0, 3.0);
-        assert!(Tuple::eq(
-            normalize(v),
-            Tuple::vector(0.26726, 0.53452, 0.80178)
-        ))
+    fn test_a_csg_shape_has_a_bounding_box_that_contains_its_children() {
+        let left = sphere();
+        let mut right = sphere();
+        right.transform = translation(2.0, 3.0, 4.0);
+        let c = csg(CsgOperation::Difference, Box::new(left), Box::new(right));
+        let b = c.bounds();
+        assert_relative_eq!(b.min, Tuple::point(-1.0, -1.0, -1.0), epsilon = ERR);
+        assert_relative_eq!(b.max, Tuple::point(3.0, 4.0, 5.0), epsilon = ERR);
     }
+
     #[test]
-    fn test_normalizing3() {
-        let v = Tuple::vector(1.0, 2.0, 3.0);
-        let norm = normalize(v);
-        assert_eq!(magnitude(norm), 1.0)
+    fn test_intersecting_a_ray_with_a_bounding_box() {
+        let b = bounding_box(Tuple::point(5.0, -2.0, 0.0), Tuple::point(11.0, 4.0, 7.0));
+        let cases = [
+            (
+                Tuple::point(15.0, 1.0, 2.0),
+                Tuple::vector(-1.0, 0.0, 0.0),
+                true,
+            ),
+            (
+                Tuple::point(-5.0, -1.0, 4.0),
+                Tuple::vector(1.0, 0.0, 0.0),
+                true,
+            ),
+            (
+                Tuple::point(7.0, 6.0, 5.0),
+                Tuple::vector(0.0, -1.0, 0.0),
+                true,
+            ),
+            (
+                Tuple::point(9.0, -5.0, 6.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+                true,
+            ),
+            (
+                Tuple::point(8.0, 2.0, 12.0),
+                Tuple::vector(0.0, 0.0, -1.0),
+                true,
+            ),
+            (
+                Tuple::point(6.0, 0.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+                true,
+            ),
+            (
+                Tuple::point(8.0, 1.0, 3.5),
+                Tuple::vector(0.0, 0.0, 1.0),
+                true,
+            ),
+            (
+                Tuple::point(9.0, -1.0, -8.0),
+                Tuple::vector(2.0, 4.0, 6.0),
+                false,
+            ),
+            (
+                Tuple::point(8.0, 3.0, -4.0),
+                Tuple::vector(6.0, 2.0, 4.0),
+                false,
+            ),
+            (
+                Tuple::point(9.0, -1.0, -2.0),
+                Tuple::vector(4.0, 6.0, 2.0),
+                false,
+            ),
+            (
+                Tuple::point(4.0, 0.0, 9.0),
+                Tuple::vector(0.0, 0.0, -1.0),
+                false,
+            ),
+            (
+                Tuple::point(8.0, 6.0, -1.0),
+                Tuple::vector(0.0, -1.0, 0.0),
+                false,
+            ),
+            (
+                Tuple::point(12.0, 5.0, 4.0),
+                Tuple::vector(-1.0, 0.0, 0.0),
+                false,
+            ),
+        ];
+        for (origin, direction, result) in cases.iter() {
+            let r = ray(*origin, normalize(*direction));
+            assert_eq!(b.intersects(&r), *result);
+        }
     }
+
     #[test]
-    fn test_dot_product() {
-        let a = Tuple::vector(1.0, 2.0, 3.0);
-        let b = Tuple::vector(2.0, 3.0, 4.0);
-        assert_eq!(dot(a, b), 20.0)
+    fn test_intersecting_ray_and_group_checks_the_bounding_box() {
+        let mut g = group();
+        g.add_child(Box::new(sphere_at(0.0, 3.0, 0.0)));
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(!g.bounds().intersects(&r));
+        assert!(g.intersect(&r).is_empty());
+        let r = ray(Tuple::point(0.0, 3.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert!(g.bounds().intersects(&r));
+        assert_eq!(g.intersect(&r).len(), 2);
     }
+
     #[test]
-    fn test_cross_product() {
-        let a = Tuple::vector(1.0, 2.0, 3.0);
-        let b = Tuple::vector(2.0, 3.0, 4.0);
-        assert_eq!(cross(a, b), Tuple::vector(-1.0, 2.0, -1.0));
-        assert_eq!(cross(b, a), Tuple::vector(1.0, -2.0, 1.0));
+    fn test_splitting_bounding_boxes() {
+        let cases = [
+            // a perfect cube
+            (
+                (-1.0, -4.0, -5.0),
+                (9.0, 6.0, 5.0),
+                (4.0, 6.0, 5.0),
+                (4.0, -4.0, -5.0),
+            ),
+            // wide along x
+            (
+                (-1.0, -2.0, -3.0),
+                (9.0, 5.5, 3.0),
+                (4.0, 5.5, 3.0),
+                (4.0, -2.0, -3.0),
+            ),
+            // wide along y
+            (
+                (-1.0, -2.0, -3.0),
+                (5.0, 8.0, 3.0),
+                (5.0, 3.0, 3.0),
+                (-1.0, 3.0, -3.0),
+            ),
+            // wide along z
+            (
+                (-1.0, -2.0, -3.0),
+                (5.0, 3.0, 7.0),
+                (5.0, 3.0, 2.0),
+                (-1.0, -2.0, 2.0),
+            ),
+        ];
+        for (min, max, left_max, right_min) in cases.iter() {
+            let b = bounding_box(
+                Tuple::point(min.0, min.1, min.2),
+                Tuple::point(max.0, max.1, max.2),
+            );
+            let (left, right) = b.split();
+            assert_eq!(left.min, b.min);
+            assert_eq!(left.max, Tuple::point(left_max.0, left_max.1, left_max.2));
+            assert_eq!(
+                right.min,
+                Tuple::point(right_min.0, right_min.1, right_min.2)
+            );
+            assert_eq!(right.max, b.max);
+        }
     }
-    #[test]
-    fn test_colors() {
-        let c = color(-0.5, 0.4, 1.7);
-        assert_eq!(c.red, -0.5);
-        assert_eq!(c.green, 0.4);
-        assert_eq!(c.blue, 1.7);
+
+    fn sphere_at(x: f64, y: f64, z: f64) -> Sphere {
+        let mut s = sphere();
+        s.transform = translation(x, y, z);
+        s
     }
+
     #[test]
-    fn test_colors_add() {
-        let c1 = color(0.9, 0.6, 0.75);
-        let c2 = color(0.7, 0.1, 0.25);
-        assert_eq!(c1 + c2, color(1.6, 0.7, 1.0))
+    fn test_partitioning_a_groups_children() {
+        let mut g = group();
+        g.add_child(Box::new(sphere_at(-2.0, 0.0, 0.0)));
+        g.add_child(Box::new(sphere_at(2.0, 0.0, 0.0)));
+        g.add_child(Box::new(sphere()));
+        let (left, right) = g.partition_children();
+        assert_eq!(g.children().len(), 1);
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].transform(), &translation(-2.0, 0.0, 0.0));
+        assert_eq!(right.len(), 1);
+        assert_eq!(right[0].transform(), &translation(2.0, 0.0, 0.0));
     }
+
     #[test]
-    fn test_mult_scalar() {
-        let c = color(0.2, 0.3, 0.4);
-        assert_eq!(c * 2.0, color(0.4, 0.6, 0.8))
+    fn test_creating_a_subgroup_from_a_list_of_children() {
+        let mut g = group();
+        g.make_subgroup(vec![Box::new(sphere()), Box::new(sphere())]);
+        assert_eq!(g.children().len(), 1);
+        assert_eq!(g.children()[0].bounds(), sphere().bounds());
     }
+
     #[test]
-    fn test_mult_colors() {
-        let c1 = color(1.0, 0.2, 0.4);
-        let c2 = color(0.9, 1.0, 0.1);
-        assert_eq!(c1 * c2, color(0.9, 0.2, 0.04))
+    fn test_subdividing_a_group_partitions_its_children() {
+        let mut g = group();
+        g.add_child(Box::new(sphere_at(-2.0, -2.0, 0.0)));
+        g.add_child(Box::new(sphere_at(-2.0, 2.0, 0.0)));
+        g.add_child(Box::new(scaled_sphere(4.0)));
+        g.divide(1);
+        // the big sphere straddles both halves; the small ones end up in a
+        // subgroup, which is itself divided into one group per sphere
+        assert_eq!(g.children().len(), 2);
+        assert_eq!(g.children()[0].transform(), &scaling(4.0, 4.0, 4.0));
+        let r = ray(Tuple::point(-2.0, -2.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        // both the big sphere and the small one at (-2, -2, 0)
+        assert_eq!(g.intersect(&r).len(), 4);
     }
-    #[test]
-    fn test_canvas() {
-        let c = canvas(10, 20);
-        assert_eq!(c.width, 10);
-        assert_eq!(c.height, 20);
-        for x in 0..c.pixels.len() {
-            for y in 0..c.pixels[x].len() {
-                assert_eq!(c.pixels[x as usize][y as usize], color(0.0, 0.0, 0.0));
-            }
-        }
+
+    fn scaled_sphere(k: f64) -> Sphere {
+        let mut s = sphere();
+        s.transform = scaling(k, k, k);
+        s
     }
+
     #[test]
-    fn test_writing_pixels() {
-        let mut c = canvas(10, 20);
-        let red = color(1.0, 0.0, 0.0);
-        write_pixel(&mut c, 2, 3, red);
-        assert_eq!(pixel_at(&mut c, 2, 3), red);
+    fn test_subdividing_a_group_with_too_few_children() {
+        let mut sub = group();
+        sub.add_child(Box::new(sphere_at(-2.0, 0.0, 0.0)));
+        sub.add_child(Box::new(sphere_at(2.0, 1.0, 0.0)));
+        sub.add_child(Box::new(sphere_at(2.0, -1.0, 0.0)));
+        let mut g = group();
+        g.add_child(Box::new(sub));
+        g.add_child(Box::new(sphere()));
+        g.divide(3);
+        // the top group is below the threshold and is left alone, while
+        // the subgroup is split into a group for each side
+        assert_eq!(g.children().len(), 2);
+        let r = ray(Tuple::point(2.0, 1.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(g.intersect(&r).len(), 2);
+        let r = ray(Tuple::point(-2.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(g.intersect(&r).len(), 2);
     }
 
     #[test]
-    fn test_costructing_the_ppm_header() {
-        let c = canvas(5, 3);
-        let ppm = canvas_to_ppm(c);
-        let want = r#"P3
-5 3
-255
-"#;
-        let mut first_four = String::new();
-        for line in ppm.lines().take(3) {
-            first_four.push_str(line);
-            first_four.push('\n');
+    fn test_subdividing_a_divided_groups_children_keeps_transforms() {
+        let mut g = group();
+        for i in 0..20 {
+            g.add_child(Box::new(sphere_at(i as f64 * 3.0, 0.0, 0.0)));
+        }
+        g.set_transform(translation(0.0, 10.0, 0.0));
+        g.divide(4);
+        for i in 0..20 {
+            let r = ray(
+                Tuple::point(i as f64 * 3.0, 10.0, -5.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+            );
+            let xs = g.intersect(&r);
+            assert_eq!(xs.len(), 2);
+            assert_eq!(xs[0].t, 4.0);
+            let n = xs[0].object.normal_at(r.position(xs[0].t), &xs[0]);
+            assert_relative_eq!(n, Tuple::vector(0.0, 0.0, -1.0), epsilon = ERR);
         }
-
-        assert_eq!(first_four, want)
     }
 
     #[test]
-    fn test_ppm_pixel_data() {
-        let mut c = canvas(5, 3);
-        let c1 = color(1.5, 0.0, 0.0);
-        let c2 = color(0.0, 0.5, 0.0);
-        let c3 = color(-0.5, 0.0, 1.0);
-        write_pixel(&mut c, 0, 0, c1);
-        write_pixel(&mut c, 2, 1, c2);
-        write_pixel(&mut c, 4, 2, c3);
-        let ppm = canvas_to_ppm(c);
-        let mut four_to_six = String::new();
-        let want = r#"255 0 0 0 0 0 0 0 0 0 0 0 0 0 0
-0 0 0 0 0 0 0 128 0 0 0 0 0 0 0
-0 0 0 0 0 0 0 0 0 0 0 0 0 0 255
-"#;
-        for line in ppm.lines().skip(3).take(4) {
-            four_to_six.push_str(line);
-            four_to_six.push('\n');
-        }
-        assert_eq!(four_to_six, want)
+    fn test_subdividing_a_csg_shape_subdivides_its_children() {
+        let mut left = group();
+        left.add_child(Box::new(sphere_at(-1.5, 0.0, 0.0)));
+        left.add_child(Box::new(sphere_at(1.5, 0.0, 0.0)));
+        let mut right = group();
+        right.add_child(Box::new(sphere_at(0.0, 0.0, -1.5)));
+        right.add_child(Box::new(sphere_at(0.0, 0.0, 1.5)));
+        let mut c = csg(CsgOperation::Difference, Box::new(left), Box::new(right));
+        c.divide(1);
+        let r = ray(Tuple::point(-1.5, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(c.intersect(&r).len(), 2);
     }
 
     #[test]
-    fn test_splitting_long_lines_in_ppm() {
+    fn test_writing_a_plain_ppm_matches_canvas_to_ppm() {
         let mut c = canvas(10, 2);
-        let col = color(1.0, 0.8, 0.6);
-        for x in 0..c.pixels.len() {
-            for y in 0..c.pixels[x].len() {
-                write_pixel(&mut c, x as i64, y as i64, col);
-            }
-        }
-        let want = r#"255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204
-153 255 204 153 255 204 153 255 204 153 255 204 153
-255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204
-153 255 204 153 255 204 153 255 204 153 255 204 153
-"#;
-        let ppm = canvas_to_ppm(c);
-        let mut four_to_seven = String::new();
-        for line in ppm.lines().skip(3).take(4) {
-            four_to_seven.push_str(line);
-            four_to_seven.push('\n');
-        }
-        assert_eq!(four_to_seven, want)
+        write_pixel(&mut c, 0, 0, color(1.0, 0.8, 0.6));
+        write_pixel(&mut c, 9, 1, color(0.0, 0.5, 1.0));
+        let mut out = Vec::new();
+        write_ppm(&c, PpmFormat::Plain, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), canvas_to_ppm(c));
     }
 
     #[test]
-    fn test_constructing_matrix() {
-        let m = DMatrix::from_row_slice(
-            4,
-            4,
-            &[
-                1.0, 2.0, 3.0, 4.0, 5.5, 6.5, 7.5, 8.5, 9.0, 10.0, 11.0, 12.0, 13.5, 14.5, 15.5,
-                16.5,
-            ],
-        );
-        assert_eq!(m[(0, 0)], 1.0);
-        assert_eq!(m[(0, 3)], 4.0);
-        assert_eq!(m[(1, 0)], 5.5);
-        assert_eq!(m[(1, 2)], 7.5);
-        assert_eq!(m[(2, 2)], 11.0);
-        assert_eq!(m[(3, 0)], 13.5);
-        assert_eq!(m[(3, 2)], 15.5);
+    fn test_writing_a_raw_ppm() {
+        let mut c = canvas(2, 2);
+        write_pixel(&mut c, 0, 0, color(1.5, 0.0, 0.0));
+        write_pixel(&mut c, 1, 0, color(0.0, 0.5, 0.0));
+        write_pixel(&mut c, 1, 1, color(-0.5, 0.0, 1.0));
+        let mut out = Vec::new();
+        write_ppm(&c, PpmFormat::Raw, &mut out).unwrap();
+        let mut want = b"P6\n2 2\n255\n".to_vec();
+        want.extend_from_slice(&[255, 0, 0, 0, 128, 0]);
+        want.extend_from_slice(&[0, 0, 0, 0, 0, 255]);
+        assert_eq!(out, want);
     }
 
     #[test]
-    fn test_constructing_2dmatrix() {
-        let m = DMatrix::from_row_slice(2, 2, &[-3, 5, 1, -2]);
-        assert_eq!(m[(0, 0)], -3);
-        assert_eq!(m[(0, 1)], 5);
-        assert_eq!(m[(1, 0)], 1);
-        assert_eq!(m[(1, 1)], -2);
+    fn test_writing_a_ppm_reports_io_errors() {
+        let c = canvas(4, 4);
+        let mut buf = [0u8; 8];
+        let mut out = &mut buf[..];
+        assert!(write_ppm(&c, PpmFormat::Raw, &mut out).is_err());
     }
 
     #[test]
-    fn test_multiplying_matrices() {
-        let a = DMatrix::from_row_slice(4, 4, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 8, 7, 6, 5, 4, 3, 2]);
-        let b = DMatrix::from_row_slice(4, 4, &[-2, 1, 2, 3, 3, 2, 1, -1, 4, 3, 6, 5, 1, 2, 7, 8]);
-        assert_eq!(
-            a * b,
-            DMatrix::from_row_slice(
-                4,
-                4,
-                &[20, 22, 50, 48, 44, 54, 114, 108, 40, 58, 110, 102, 16, 26, 46, 42]
-            )
-        );
+    fn test_reading_a_file_with_the_wrong_magic_number() {
+        let ppm = b"P32\n1 1\n255\n0 0 0\n";
+        match read_ppm(&mut &ppm[..]) {
+            Err(PpmError::UnsupportedFormat) => (),
+            other => panic!("expected UnsupportedFormat, got {:?}", other.map(|_| ())),
+        }
     }
 
     #[test]
-    fn test_multiplication_by_tuple() {
-        let a = DMatrix::from_row_slice(4, 4, &[1, 2, 3, 4, 2, 4, 4, 2, 8, 6, 4, 1, 0, 0, 0, 1]);
-        let b = DMatrix::from_row_slice(4, 1, &[1, 2, 3, 1]);
-        assert_eq!(a * b, DMatrix::from_row_slice(4, 1, &[18, 24, 33, 1]));
+    fn test_reading_a_ppm_returns_a_canvas_of_the_right_size() {
+        let mut ppm = b"P3\n10 2\n255\n".to_vec();
+        for _ in 0..20 {
+            ppm.extend_from_slice(b"0 0 0\n");
+        }
+        let c = read_ppm(&mut &ppm[..]).unwrap();
+        assert_eq!(c.width(), 10);
+        assert_eq!(c.height(), 2);
     }
 
     #[test]
-    fn test_multiplicative_identity() {
-        let a = DMatrix::from_row_slice(4, 4, &[0, 1, 2, 4, 1, 2, 4, 8, 2, 4, 8, 16, 4, 8, 16, 32]);
-        let id = DMatrix::identity(4, 4);
-        assert_eq!(a.clone() * id, a);
+    fn test_reading_pixel_data_from_a_ppm_file() {
+        let ppm = b"P3
+4 3
+255
+255 127 0  0 127 255  127 255 0  255 255 255
+0 0 0  255 0 0  0 255 0  0 0 255
+255 255 0  0 255 255  255 0 255  127 127 127
+";
+        let c = read_ppm(&mut &ppm[..]).unwrap();
+        let cases = [
+            (0, 0, color(1.0, 0.498, 0.0)),
+            (1, 0, color(0.0, 0.498, 1.0)),
+            (2, 0, color(0.498, 1.0, 0.0)),
+            (3, 0, color(1.0, 1.0, 1.0)),
+            (0, 1, color(0.0, 0.0, 0.0)),
+            (1, 1, color(1.0, 0.0, 0.0)),
+            (2, 1, color(0.0, 1.0, 0.0)),
+            (3, 1, color(0.0, 0.0, 1.0)),
+            (0, 2, color(1.0, 1.0, 0.0)),
+            (1, 2, color(0.0, 1.0, 1.0)),
+            (2, 2, color(1.0, 0.0, 1.0)),
+            (3, 2, color(0.498, 0.498, 0.498)),
+        ];
+        for (x, y, want) in cases.iter() {
+            assert_relative_eq!(pixel_at(&c, *x, *y), *want, epsilon = ERR);
+        }
     }
 
     #[test]
-    fn test_transpose() {
-        let a = DMatrix::from_row_slice(4, 4, &[0, 9, 3, 0, 9, 8, 0, 8, 1, 8, 5, 3, 0, 0, 5, 8]);
-        let t = a.transpose();
-        assert_eq!(
-            t,
-            DMatrix::from_row_slice(4, 4, &[0, 9, 1, 0, 9, 8, 8, 0, 3, 0, 5, 5, 0, 8, 3, 8])
-        );
+    fn test_ppm_parsing_ignores_comment_lines() {
+        let ppm = b"P3
+# this is a comment
+2 1
+# this, too
+255
+# another comment
+255 255 255
+# oh, no, comments in the pixel data!
+255 0 255
+";
+        let c = read_ppm(&mut &ppm[..]).unwrap();
+        assert_relative_eq!(pixel_at(&c, 0, 0), color(1.0, 1.0, 1.0), epsilon = ERR);
+        assert_relative_eq!(pixel_at(&c, 1, 0), color(1.0, 0.0, 1.0), epsilon = ERR);
     }
 
     #[test]
-    fn test_id_transpose() {
-        let id: Matrix<i64, Dynamic, Dynamic, VecStorage<i64, Dynamic, Dynamic>> =
-            DMatrix::identity(4, 4);
-        assert_eq!(id.clone(), id.transpose());
+    fn test_ppm_parsing_allows_an_rgb_triple_to_span_lines() {
+        let ppm = b"P3
+1 1
+255
+51
+153
+
+204
+";
+        let c = read_ppm(&mut &ppm[..]).unwrap();
+        assert_relative_eq!(pixel_at(&c, 0, 0), color(0.2, 0.6, 0.8), epsilon = ERR);
     }
 
     #[test]
-    fn test_determinant() {
-        let a = DMatrix::from_row_slice(2, 2, &[1.0, 5.0, -3.0, 2.0]);
-        assert_eq!(a.determinant(), 17.0);
+    fn test_ppm_parsing_respects_the_scale_setting() {
+        let ppm = b"P3
+2 2
+100
+100 100 100  50 50 50
+75 50 25  0 0 0
+";
+        let c = read_ppm(&mut &ppm[..]).unwrap();
+        assert_relative_eq!(pixel_at(&c, 0, 1), color(0.75, 0.5, 0.25), epsilon = ERR);
     }
 
     #[test]
-    fn test_submatrix() {
-        let a = DMatrix::from_row_slice(3, 3, &[1.0, 5.0, 0.0, -3.0, 2.0, 7.0, 0.0, 6.0, -3.0]);
-        assert_eq!(
-            submatrix(&a, 0, 2),
-            DMatrix::from_row_slice(2, 2, &[-3.0, 2.0, 0.0, 6.0])
+    fn test_reading_a_raw_ppm() {
+        let mut ppm = b"P6 # raw\n2 1\n255\n".to_vec();
+        ppm.extend_from_slice(&[255, 0, 51, 10, 32, 35]);
+        let c = read_ppm(&mut &ppm[..]).unwrap();
+        assert_relative_eq!(pixel_at(&c, 0, 0), color(1.0, 0.0, 0.2), epsilon = ERR);
+        // sample bytes that look like whitespace or comments are still data
+        assert_relative_eq!(
+            pixel_at(&c, 1, 0),
+            color(10.0 / 255.0, 32.0 / 255.0, 35.0 / 255.0),
+            epsilon = ERR
         );
     }
 
     #[test]
-    fn test_submatrix_2() {
-        let a = DMatrix::from_row_slice(
-            4,
-            4,
-            &[
-                -6.0, 1.0, 1.0, 6.0, -8.0, 5.0, 8.0, 6.0, -1.0, 0.0, 8.0, 2.0, -7.0, 1.0, -1.0, 1.0,
-            ],
-        );
-        assert_eq!(
-            submatrix(&a, 2, 1),
-            DMatrix::from_row_slice(3, 3, &[-6.0, 1.0, 6.0, -8.0, 8.0, 6.0, -7.0, -1.0, 1.0])
-        );
+    fn test_reading_a_raw_ppm_with_two_byte_samples() {
+        let mut ppm = b"P6\n1 1\n1000\n".to_vec();
+        ppm.extend_from_slice(&[0x03, 0xe8, 0x01, 0xf4, 0x00, 0x00]);
+        let c = read_ppm(&mut &ppm[..]).unwrap();
+        assert_relative_eq!(pixel_at(&c, 0, 0), color(1.0, 0.5, 0.0), epsilon = ERR);
     }
 
     #[test]
-    fn test_minor() {
-        let a = DMatrix::from_row_slice(3, 3, &[3.0, 5.0, 0.0, 2.0, -1.0, -7.0, 6.0, -1.0, 5.0]);
-        let b = submatrix(&a, 1, 0);
-        assert_eq!(b.determinant(), 25.0);
-        assert_eq!(minor(&a, 1, 0), 25.0);
+    fn test_a_written_ppm_reads_back_into_the_same_canvas() {
+        let mut c = canvas(10, 3);
+        write_pixel(&mut c, 0, 0, color(1.0, 0.8, 0.6));
+        write_pixel(&mut c, 9, 2, color(0.2, 0.4, 1.0));
+        for format in [PpmFormat::Plain, PpmFormat::Raw].iter() {
+            let mut out = Vec::new();
+            write_ppm(&c, *format, &mut out).unwrap();
+            let back = read_ppm(&mut &out[..]).unwrap();
+            assert_relative_eq!(pixel_at(&back, 0, 0), color(1.0, 0.8, 0.6), epsilon = ERR);
+            assert_relative_eq!(pixel_at(&back, 9, 2), color(0.2, 0.4, 1.0), epsilon = ERR);
+        }
     }
 
     #[test]
-    fn test_cofactor() {
-        let a = DMatrix::from_row_slice(3, 3, &[3.0, 5.0, 0.0, 2.0, -1.0, -7.0, 6.0, -1.0, 5.0]);
-        assert_eq!(minor(&a, 0, 0), -12.0);
-        assert_eq!(cofactor(&a, 0, 0), -12.0);
-        assert_eq!(minor(&a, 1, 0), 25.0);
-        assert_eq!(cofactor(&a, 1, 0), -25.0);
+    fn test_reading_malformed_ppm_files() {
+        let bad_header: [&[u8]; 6] = [
+            b"P3\n2\n",
+            b"P3\nten 2\n255\n",
+            b"P3\n1 1\n0\n",
+            b"P6\n1 1\n70000\n",
+            b"P6 4294967295 4294967295 255\n",
+            b"P6 4294967295 4294967295 65535\n",
+        ];
+        for ppm in bad_header.iter() {
+            match read_ppm(&mut &ppm[..]) {
+                Err(PpmError::InvalidHeader) => (),
+                other => panic!("expected InvalidHeader, got {:?}", other.map(|_| ())),
+            }
+        }
+        let truncated: [&[u8]; 2] = [b"P3\n2 1\n255\n0 0 0 0 0\n", b"P6\n2 1\n255\n\x00\x00\x00"];
+        for ppm in truncated.iter() {
+            match read_ppm(&mut &ppm[..]) {
+                Err(PpmError::Truncated) => (),
+                other => panic!("expected Truncated, got {:?}", other.map(|_| ())),
+            }
+        }
+        let bad_sample: [&[u8]; 2] = [b"P3\n1 1\n255\n0 x 0\n", b"P3\n1 1\n100\n0 101 0\n"];
+        for ppm in bad_sample.iter() {
+            match read_ppm(&mut &ppm[..]) {
+                Err(PpmError::InvalidSample) => (),
+                other => panic!("expected InvalidSample, got {:?}", other.map(|_| ())),
+            }
+        }
     }
 
     #[test]
-    fn test_cofactor_m3() {
-        let a = DMatrix::from_row_slice(3, 3, &[1.0, 2.0, 6.0, -5.0, 8.0, -4.0, 2.0, 6.0, 4.0]);
-        assert_eq!(cofactor(&a, 0, 0), 56.0);
-        assert_eq!(cofactor(&a, 0, 1), 12.0);
-        assert_eq!(cofactor(&a, 0, 2), -46.0);
-        assert_eq!(a.determinant(), -196.0);
+    fn test_png_checksums() {
+        assert_eq!(
+            crc32_update(0xffff_ffff, b"123456789") ^ 0xffff_ffff,
+            0xcbf4_3926
+        );
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
     }
 
-    #[test]
-    fn test_cofactor_m4() {
-        let a = DMatrix::from_row_slice(
-            4,
-            4,
-            &[
-                -2.0, -8.0, 3.0, 5.0, -3.0, 1.0, 7.0, 3.0, 1.0, 2.0, -9.0, 6.0, -6.0, 7.0, 7.0,
-                -9.0,
-            ],
-        );
-        assert_eq!(cofactor(&a, 0, 0), 690.0);
-        assert_eq!(cofactor(&a, 0, 1), 447.0);
-        assert_eq!(cofactor(&a, 0, 2), 210.0);
-        assert_eq!(cofactor(&a, 0, 3), 51.0);
-        assert_relative_eq!(a.determinant(), -4071.0);
+    // the chunks of a png file, checking each one's crc along the way
+    fn png_chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let mut chunks = vec![];
+        let mut pos = 8;
+        while pos < png.len() {
+            let mut len = [0; 4];
+            len.copy_from_slice(&png[pos..pos + 4]);
+            let len = u32::from_be_bytes(len) as usize;
+            let body = &png[pos + 4..pos + 8 + len];
+            let mut crc = [0; 4];
+            crc.copy_from_slice(&png[pos + 8 + len..pos + 12 + len]);
+            assert_eq!(
+                u32::from_be_bytes(crc),
+                crc32_update(0xffff_ffff, body) ^ 0xffff_ffff
+            );
+            let kind = String::from_utf8(body[..4].to_vec()).unwrap();
+            chunks.push((kind, body[4..].to_vec()));
+            pos += 12 + len;
+        }
+        chunks
+    }
+
+    // undoes zlib_stored
+    fn unstore(z: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        let mut pos = 2;
+        loop {
+            let last = z[pos] == 1;
+            let len = z[pos + 1] as usize | (z[pos + 2] as usize) << 8;
+            data.extend_from_slice(&z[pos + 5..pos + 5 + len]);
+            pos += 5 + len;
+            if last {
+                break;
+            }
+        }
+        let mut adler = [0; 4];
+        adler.copy_from_slice(&z[pos..pos + 4]);
+        assert_eq!(u32::from_be_bytes(adler), adler32(&data));
+        data
     }
 
     #[test]
-    fn test_invertible() {
-        let a = DMatrix::from_row_slice(
-            4,
-            4,
-            &[
-                6.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 6.0, 4.0, -9.0, 3.0, -7.0, 9.0, 1.0, 7.0, -6.0,
-            ],
+    fn test_writing_an_8_bit_png() {
+        let mut c = canvas(2, 2);
+        write_pixel(&mut c, 0, 0, color(1.5, 0.0, 0.0));
+        write_pixel(&mut c, 1, 0, color(0.0, 0.5, 0.0));
+        write_pixel(&mut c, 1, 1, color(-0.5, 0.0, 1.0));
+        let mut png = Vec::new();
+        write_png(&c, PngDepth::Eight, &mut png).unwrap();
+        let chunks = png_chunks(&png);
+        let kinds: Vec<&str> = chunks.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(kinds, vec!["IHDR", "IDAT", "IEND"]);
+        assert_eq!(chunks[0].1, vec![0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+        assert_eq!(
+            unstore(&chunks[1].1),
+            vec![0, 255, 0, 0, 0, 128, 0, 0, 0, 0, 0, 0, 0, 255]
         );
-
-        assert_eq!(a.determinant(), -2120.0);
-        assert_eq!(a.is_invertible(), true);
     }
 
     #[test]
-    fn test_not_invertible() {
-        let a = DMatrix::from_row_slice(
-            4,
-            4,
-            &[
-                -4.0, 2.0, -2.0, -3.0, 9.0, 6.0, 2.0, 6.0, 0.0, -5.0, 1.0, -5.0, 0.0, 0.0, 0.0, 0.0,
-            ],
+    fn test_writing_a_16_bit_png() {
+        let mut c = canvas(1, 1);
+        write_pixel(&mut c, 0, 0, color(1.0, 0.5, 2.0));
+        let mut png = Vec::new();
+        write_png(&c, PngDepth::Sixteen, &mut png).unwrap();
+        let chunks = png_chunks(&png);
+        assert_eq!(chunks[0].1[8], 16);
+        assert_eq!(
+            unstore(&chunks[1].1),
+            vec![0, 0xff, 0xff, 0x80, 0x00, 0xff, 0xff]
         );
+    }
 
-        assert_eq!(a.determinant(), 0.0);
-        assert_eq!(a.is_invertible(), false);
+    #[test]
+    fn test_png_image_data_spans_several_stored_blocks() {
+        // each scanline is 1 + 3 * 30000 bytes, more than one block holds
+        let mut c = canvas(30000, 2);
+        write_pixel(&mut c, 29999, 1, color(1.0, 1.0, 1.0));
+        let mut png = Vec::new();
+        write_png(&c, PngDepth::Eight, &mut png).unwrap();
+        let data = unstore(&png_chunks(&png)[1].1);
+        assert_eq!(data.len(), 2 * 90001);
+        assert_eq!(&data[data.len() - 3..], &[255, 255, 255]);
     }
 
     #[test]
-    fn test_not_inverse() {
-        let a = DMatrix::from_row_slice(
-            4,
-            4,
-            &[
-                -5.0, 2.0, 6.0, -8.0, 1.0, -5.0, 1.0, 8.0, 7.0, 7.0, -6.0, -7.0, 1.0, -3.0, 7.0,
-                4.0,
-            ],
+    fn test_a_written_pfm_reads_back_into_the_same_canvas() {
+        let mut c = canvas(3, 2);
+        write_pixel(&mut c, 0, 0, color(12.5, -0.25, 0.0));
+        write_pixel(&mut c, 2, 1, color(0.1, 1000.0, 3.0));
+        let mut pfm = Vec::new();
+        write_pfm(&c, &mut pfm).unwrap();
+        assert!(pfm.starts_with(b"PF\n3 2\n-1.0\n"));
+        // the bottom row comes first
+        assert_eq!(&pfm[12 + 24..12 + 28], &0.1f32.to_le_bytes());
+        let back = read_pfm(&mut &pfm[..]).unwrap();
+        assert_eq!(back.width(), 3);
+        assert_eq!(back.height(), 2);
+        assert_relative_eq!(
+            pixel_at(&back, 0, 0),
+            color(12.5, -0.25, 0.0),
+            epsilon = ERR
         );
-        let b = a.clone().try_inverse().unwrap();
-        assert_relative_eq!(a.determinant(), 532.0);
-        assert_eq!(cofactor(&a, 2, 3), -160.0);
-        assert_eq!(b[(3, 2)], -160.0 / 532.0);
-        assert_eq!(cofactor(&a, 3, 2), 105.0);
-        assert_eq!(b[(2, 3)], 105.0 / 532.0);
         assert_relative_eq!(
-            b,
-            DMatrix::from_row_slice(
-                4,
-                4,
-                #[cfg_attr(rustfmt, rustfmt_skip)]
-                &[
-                    0.21804511278195488,                    0.45112781954887216,                   0.24060150375939848,                    -0.045112781954887216,
-                    -0.8082706766917293,                    -1.456766917293233,                    -0.44360902255639095,                    0.5206766917293233,
-                    -0.07894736842105263,                   -0.22368421052631576,                  -0.05263157894736842,                    0.19736842105263158,
-                    -0.5225563909774436,                    -0.8139097744360901,                   -0.3007518796992481,                     0.306390977443609
-                ]
-            )
+            pixel_at(&back, 2, 1),
+            color(0.1, 1000.0, 3.0),
+            epsilon = ERR
         );
     }
 
     #[test]
-    fn test_inverting_id() {
-        let id: Matrix<f64, Dynamic, Dynamic, VecStorage<f64, Dynamic, Dynamic>> =
-            DMatrix::identity(4, 4);
-        if PRINT_NOTES {
-            println!("what is the inverse of the identity matrix?");
-            println!("id     : {:?}", id.clone());
-            println!("inverse: {:?}", id.clone().try_inverse().unwrap());
+    fn test_reading_a_big_endian_pfm() {
+        let mut pfm = b"PF\n1 1\n1.0\n".to_vec();
+        for v in [2.5f32, 0.5, 4.0].iter() {
+            pfm.extend_from_slice(&v.to_be_bytes());
         }
+        let c = read_pfm(&mut &pfm[..]).unwrap();
+        assert_relative_eq!(pixel_at(&c, 0, 0), color(2.5, 0.5, 4.0), epsilon = ERR);
     }
 
     #[test]
-    fn test_translation() {
-        let m = translation(4.0, 3.0, 2.0);
-        assert_eq!(
-            #[cfg_attr(rustfmt, rustfmt_skip)]
-            Matrix4::from_row_slice(&[
-                1.0, 0.0, 0.0, 4.0,
-                0.0, 1.0, 0.0, 3.0,
-                0.0, 0.0, 1.0, 2.0,
-                0.0, 0.0, 0.0, 1.0,
-            ]),
-            m
-        );
+    fn test_reading_malformed_pfm_files() {
+        match read_pfm(&mut &b"Pf\n1 1\n-1.0\n\0\0\0\0"[..]) {
+            Err(HdrError::UnsupportedFormat) => (),
+            other => panic!("expected UnsupportedFormat, got {:?}", other.map(|_| ())),
+        }
+        let bad_header: [&[u8]; 2] = [b"PF\n1 1\nbig\n", b"PF\n4294967295 4294967295\n-1.0\n"];
+        for pfm in bad_header.iter() {
+            match read_pfm(&mut &pfm[..]) {
+                Err(HdrError::InvalidHeader) => (),
+                other => panic!("expected InvalidHeader, got {:?}", other.map(|_| ())),
+            }
+        }
+        match read_pfm(&mut &b"PF\n1 1\n-1.0\n\0\0\0\0"[..]) {
+            Err(HdrError::Truncated) => (),
+            other => panic!("expected Truncated, got {:?}", other.map(|_| ())),
+        }
     }
 
     #[test]
-    fn test_transforming_a_point_by_a_translation_matrix() {
-        let t = translation(5.0, -3.0, 2.0);
-        let p = Point3::new(-3.0, 4.0, 5.0);
-        // The point goes forwards!
-        assert_eq!(t.transform_point(&p), Point3::new(2.0, 1.0, 7.0));
+    fn test_converting_colors_to_and_from_rgbe() {
+        assert_eq!(color_to_rgbe(color(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+        assert_eq!(color_to_rgbe(color(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+        assert_eq!(color_to_rgbe(color(-1.0, 0.5, 0.0)), [0, 128, 0, 128]);
+        for c in [
+            color(1.0, 0.5, 0.25),
+            color(100.0, 3.0, 0.01),
+            color(0.002, 0.001, 0.0),
+        ]
+        .iter()
+        {
+            let back = rgbe_to_color(color_to_rgbe(*c));
+            // the mantissa keeps 8 bits relative to the brightest channel
+            let tolerance = c.red.max(c.green).max(c.blue) / 128.0;
+            assert_relative_eq!(back, *c, epsilon = tolerance);
+        }
     }
 
     #[test]
-    fn test_transforming_a_point_by_the_inverse_of_a_translation_matrix() {
-        let mut t = translation(5.0, -3.0, 2.0);
-        t.try_inverse_mut();
-        let p = Point3::new(-3.0, 4.0, 5.0);
-        // The point goes backwards?!?!?
-        assert_eq!(t.transform_point(&p), Point3::new(-8.0, 7.0, 3.0));
+    fn test_run_length_encoding_hdr_channels() {
+        let mut out = vec![];
+        rle_encode(&[5, 5, 5, 5, 1, 2, 7, 7, 7], &mut out);
+        assert_eq!(out, vec![132, 5, 2, 1, 2, 131, 7]);
+        let mut out = vec![];
+        let literal: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        rle_encode(&literal, &mut out);
+        assert_eq!(out.len(), 202);
+        assert_eq!(out[0], 128);
+        assert_eq!(out[129], 72);
     }
 
     #[test]
-    fn test_translation_does_not_affect_vectors() {
-        let t = translation(5.0, -3.0, 2.0);
-        let v = Vector3::new(-3.0, 4.0, 5.0);
-        // translation does not affect vectors
-        assert_eq!(t.transform_vector(&v), v);
+    fn test_a_written_hdr_reads_back_into_the_same_canvas() {
+        // too narrow for run-length encoding, and wide enough for it
+        for width in [4, 300].iter() {
+            let mut c = canvas(*width, 3);
+            write_pixel(&mut c, 0, 0, color(12.5, 0.25, 0.0));
+            write_pixel(&mut c, 3, 2, color(0.1, 1000.0, 3.0));
+            let mut hdr = Vec::new();
+            write_hdr(&c, &mut hdr).unwrap();
+            assert!(hdr.starts_with(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 3 +X "));
+            let back = read_hdr(&mut &hdr[..]).unwrap();
+            assert_eq!(back.width(), *width);
+            assert_eq!(back.height(), 3);
+            assert_relative_eq!(pixel_at(&back, 0, 0), color(12.5, 0.25, 0.0), epsilon = 0.1);
+            assert_relative_eq!(
+                pixel_at(&back, 3, 2),
+                color(0.1, 1000.0, 3.0),
+                epsilon = 8.0
+            );
+            assert_eq!(pixel_at(&back, 1, 1), color(0.0, 0.0, 0.0));
+        }
     }
 
     #[test]
-    fn test_scaling_a_matrix_applied_to_a_point() {
-        let t = scaling(2.0, 3.0, 4.0);
-        let p = Point3::new(-4.0, 6.0, 8.0);
-        // scaling scales out all points
-        assert_eq!(t.transform_point(&p), Point3::new(-8.0, 18.0, 32.0));
+    fn test_reading_an_hdr_with_old_style_repeats() {
+        let mut hdr = b"#?RGBE\nEXPOSURE=1.0\n\n-Y 1 +X 4\n".to_vec();
+        hdr.extend_from_slice(&[128, 64, 32, 129, 1, 1, 1, 2, 0, 0, 0, 0]);
+        let c = read_hdr(&mut &hdr[..]).unwrap();
+        for x in 0..3 {
+            assert_relative_eq!(pixel_at(&c, x, 0), color(1.0, 0.5, 0.25), epsilon = 0.01);
+        }
+        assert_eq!(pixel_at(&c, 3, 0), color(0.0, 0.0, 0.0));
     }
 
     #[test]
-    fn test_scaling_matrix_applied_to_a_vector() {
-        let t = scaling(2.0, 3.0, 4.0);
-        let v = Vector3::new(-4.0, 6.0, 8.0);
-        // scaling scales vectors as well
-        assert_eq!(t.transform_vector(&v), Vector3::new(-8.0, 18.0, 32.0));
+    fn test_reading_an_hdr_with_wide_rows_of_legacy_repeats() {
+        // each row is one pixel repeated 255 + (15 << 8) more times, so 100
+        // rows of 4000 pixels take only 1200 bytes
+        let mut hdr = b"#?RADIANCE\n\n-Y 100 +X 4000\n".to_vec();
+        for _ in 0..100 {
+            hdr.extend_from_slice(&[128, 64, 32, 129, 1, 1, 1, 255, 1, 1, 1, 15]);
+        }
+        let c = read_hdr(&mut &hdr[..]).unwrap();
+        assert_eq!(c.width(), 4000);
+        assert_eq!(c.height(), 100);
+        assert_relative_eq!(
+            pixel_at(&c, 3999, 99),
+            color(1.0, 0.5, 0.25),
+            epsilon = 0.01
+        );
+        // but a row can't claim more than 128 pixels for each byte of data
+        let mut hdr = b"#?RADIANCE\n\n-Y 1 +X 1537\n".to_vec();
+        hdr.extend_from_slice(&[128, 64, 32, 129, 1, 1, 1, 255, 1, 1, 1, 15]);
+        match read_hdr(&mut &hdr[..]) {
+            Err(HdrError::Truncated) => (),
+            other => panic!("expected Truncated, got {:?}", other.map(|_| ())),
+        }
     }
 
     #[test]
-    fn test_reflection_is_scaling_by_a_negative_value() {
-        let t = scaling(-1.0, 1.0, 1.0);
-        let p = Point3::new(2.0, 3.0, 4.0);
-        // reflects a point over the x axis
-        assert_eq!(t.transform_point(&p), Point3::new(-2.0, 3.0, 4.0));
+    fn test_reading_malformed_hdr_files() {
+        let unsupported: [&[u8]; 2] = [
+            b"P6\n1 1\n255\n",
+            b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\0\0\0\0",
+        ];
+        for hdr in unsupported.iter() {
+            match read_hdr(&mut &hdr[..]) {
+                Err(HdrError::UnsupportedFormat) => (),
+                other => panic!("expected UnsupportedFormat, got {:?}", other.map(|_| ())),
+            }
+        }
+        let bad_header: [&[u8]; 4] = [
+            b"#?RADIANCE\n",
+            b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0",
+            b"#?RADIANCE\n\n-Y 18446744073709551615 +X 2\n\0\0\0\0",
+            b"#?RADIANCE\n\n-Y 18446744073709551615 +X 1\n\0\0\0\0",
+        ];
+        for hdr in bad_header.iter() {
+            match read_hdr(&mut &hdr[..]) {
+                Err(HdrError::InvalidHeader) => (),
+                other => panic!("expected InvalidHeader, got {:?}", other.map(|_| ())),
+            }
+        }
+        let truncated: [&[u8]; 5] = [
+            b"#?RADIANCE\n\n-Y 1 +X 2\n\0\0\0\0",
+            b"#?RADIANCE\n\n-Y 1 +X 8\n\x02\x02\x00\x08\x88",
+            // far more pixels than a few bytes could ever hold
+            b"#?RADIANCE\n\n-Y 100000 +X 100000\n\0\0\0\0",
+            b"#?RADIANCE\n\n-Y 1 +X 10000000000\n\0\0\0\0",
+            b"#?RADIANCE\n\n-Y 0 +X 1000000000000000000\n",
+        ];
+        for hdr in truncated.iter() {
+            match read_hdr(&mut &hdr[..]) {
+                Err(HdrError::Truncated) => (),
+                other => panic!("expected Truncated, got {:?}", other.map(|_| ())),
+            }
+        }
     }
 
     #[test]
-    fn test_rotating_a_point_around_the_x_axis() {
-        let p = Point3::new(0.0, 1.0, 0.0);
-        let half_quarter = rotation_x(std::f64::consts::FRAC_PI_4);
-        let full_quarter = rotation_x(std::f64::consts::FRAC_PI_2);
-        assert_relative_eq!(
-            half_quarter.transform_point(&p),
-            Point3::new(0.0, (2.0).sqrt() / 2.0, (2.0).sqrt() / 2.0)
-        );
-        assert_relative_eq!(full_quarter.transform_point(&p), Point3::new(0.0, 0.0, 1.0));
+    fn test_subdividing_a_group_of_coincident_children_terminates() {
+        let p = Tuple::point(1.0, 1.0, 1.0);
+        let mut g = group();
+        for _ in 0..8 {
+            g.add_child(Box::new(triangle(p, p, p)));
+        }
+        g.divide(2);
+        assert_eq!(g.children().len(), 8);
     }
 
     #[test]
-    fn test_rotating_a_point_around_the_y_axis() {
-        let p = Point3::new(0.0, 0.0, 1.0);
-        let half_quarter = rotation_y(std::f64::consts::FRAC_PI_4);
-        let full_quarter = rotation_y(std::f64::consts::FRAC_PI_2);
-        assert_relative_eq!(
-            half_quarter.transform_point(&p),
-            Point3::new((2.0).sqrt() / 2.0, 0.0, (2.0).sqrt() / 2.0)
-        );
-        assert_relative_eq!(full_quarter.transform_point(&p), Point3::new(1.0, 0.0, 0.0));
+    fn test_obj_files_with_degenerate_faces_can_be_divided() {
+        let file = r#"v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+f 1 1 1
+f 1 1 1
+f 1 1 1
+f 1 1 1
+f 1 1 1
+f 1 1 1
+f 1 1 1
+f 1 1 1
+"#;
+        let g = parse_obj_file(file).obj_to_group();
+        let r = ray(Tuple::point(0.25, 0.25, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        assert_eq!(g.intersect(&r).len(), 1);
     }
 
     #[test]
-    fn test_rotating_a_point_around_the_z_axis() {
-        let p = Point3::new(0.0, 1.0, 0.0);
-        let half_quarter = rotation_z(std::f64::consts::FRAC_PI_4);
-        let full_quarter = rotation_z(std::f64::consts::FRAC_PI_2);
-        assert_relative_eq!(
-            half_quarter.transform_point(&p),
-            Point3::new(-1.0 * (2.0).sqrt() / 2.0, (2.0).sqrt() / 2.0, 0.0)
-        );
-        assert_relative_eq!(
-            full_quarter.transform_point(&p),
-            Point3::new(-1.0, 0.0, 0.0)
+    fn test_a_ray_strikes_a_small_triangle() {
+        // small enough that its determinant is far below ERR
+        let t = triangle(
+            Tuple::point(0.0, 0.01, 0.0),
+            Tuple::point(-0.01, 0.0, 0.0),
+            Tuple::point(0.01, 0.0, 0.0),
         );
+        let r = ray(Tuple::point(0.0, 0.005, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = t.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_relative_eq!(xs[0].t, 2.0, epsilon = ERR);
     }
 
     #[test]
-    fn test_shearing_x_in_proportion_to_y() {
-        let transform = shearing(
-            1.0, 0.0, 0.0,
-            0.0, 0.0, 0.0
-        );
-        let p = Point3::new(2.0, 3.0, 4.0);
-        assert_relative_eq!(
-            transform.transform_point(&p),
-            Point3::new(5.0, 3.0, 4.0)
-        )
+    fn test_intersecting_a_scaled_up_cylinder() {
+        let mut cyl = cylinder();
+        cyl.transform = scaling(40.0, 40.0, 40.0);
+        let r = ray(Tuple::point(0.0, 0.0, -100.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = cyl.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_relative_eq!(xs[0].t, 60.0, epsilon = ERR);
+        assert_relative_eq!(xs[1].t, 140.0, epsilon = ERR);
+        // and its caps
+        cyl.minimum = -1.0;
+        cyl.maximum = 1.0;
+        cyl.closed = true;
+        let r = ray(Tuple::point(0.0, 100.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+        let xs = cyl.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_relative_eq!(xs[0].t, 60.0, epsilon = ERR);
+        assert_relative_eq!(xs[1].t, 140.0, epsilon = ERR);
     }
 
     #[test]
-    fn test_shearing_x_in_proportion_to_z() {
-        let transform = shearing(
-            0.0, 1.0, 0.0,
-            0.0, 0.0, 0.0
+    fn test_intersecting_a_scaled_up_cone() {
+        let mut shape = cone();
+        shape.transform = scaling(40.0, 40.0, 40.0);
+        // the same ray as the unscaled cone's, scaled with it
+        let r = ray(
+            Tuple::point(40.0, 40.0, -200.0),
+            normalize(Tuple::vector(-0.5, -1.0, 1.0)),
         );
-        let p = Point3::new(2.0, 3.0, 4.0);
-        assert_relative_eq!(
-            transform.transform_point(&p),
-            Point3::new(6.0, 3.0, 4.0)
-        )
+        let unscaled = ray(
+            Tuple::point(1.0, 1.0, -5.0),
+            normalize(Tuple::vector(-0.5, -1.0, 1.0)),
+        );
+        let unit = cone();
+        let xs = shape.intersect(&r);
+        let want = unit.intersect(&unscaled);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(want.len(), 2);
+        assert_relative_eq!(xs[0].t, want[0].t * 40.0, epsilon = ERR);
+        assert_relative_eq!(xs[1].t, want[1].t * 40.0, epsilon = ERR);
     }
 
     #[test]
-    fn test_shearing_y_in_proportion_to_x() {
-        let transform = shearing(
-            0.0, 0.0, 1.0,
-            0.0, 0.0, 0.0
+    fn test_intersecting_a_scaled_up_cube() {
+        let mut c = cube();
+        c.transform = scaling(2000.0, 2000.0, 2000.0);
+        let r = ray(
+            Tuple::point(0.0, 0.0, -5000.0),
+            Tuple::vector(0.0, 0.0, 1.0),
         );
-        let p = Point3::new(2.0, 3.0, 4.0);
-        assert_relative_eq!(
-            transform.transform_point(&p),
-            Point3::new(2.0, 5.0, 4.0)
-        )
+        let xs = c.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_relative_eq!(xs[0].t, 3000.0, epsilon = ERR);
+        assert_relative_eq!(xs[1].t, 7000.0, epsilon = ERR);
     }
 
     #[test]
-    fn test_shearing_y_in_proportion_to_z() {
-        let transform = shearing(
-            0.0, 0.0, 0.0,
-            1.0, 0.0, 0.0
+    fn test_a_scaled_up_group_still_culls_by_its_bounds() {
+        let mut g = group();
+        g.add_child(Box::new(sphere_at(3.0, 0.0, 0.0)));
+        g.set_transform(scaling(2000.0, 2000.0, 2000.0));
+        let inv = g.transform().try_inverse().unwrap();
+        let miss = ray(
+            Tuple::point(0.0, 0.0, -5000.0),
+            Tuple::vector(0.0, 0.0, 1.0),
         );
-        let p = Point3::new(2.0, 3.0, 4.0);
-        assert_relative_eq!(
-            transform.transform_point(&p),
-            Point3::new(2.0, 7.0, 4.0)
-        )
+        assert!(!g.bounds().intersects(&miss.transform(&inv)));
+        let hit = ray(
+            Tuple::point(6000.0, 0.0, -5000.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+        assert!(g.bounds().intersects(&hit.transform(&inv)));
+        assert_eq!(g.intersect(&hit).len(), 2);
     }
 
     #[test]
-    fn test_shearing_z_in_proportion_to_x() {
-        let transform = shearing(
-            0.0, 0.0, 0.0,
-            0.0, 1.0, 0.0
-        );
-        let p = Point3::new(2.0, 3.0, 4.0);
-        assert_relative_eq!(
-            transform.transform_point(&p),
-            Point3::new(2.0, 3.0, 6.0)
-        )
+    fn test_intersecting_a_scaled_up_plane() {
+        let mut p = plane();
+        p.transform = scaling(5000.0, 5000.0, 5000.0);
+        let r = ray(Tuple::point(0.0, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+        let xs = p.intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_relative_eq!(xs[0].t, 1.0, epsilon = ERR);
+    }
+
+    // a sphere that counts how often it is asked for intersections
+    #[derive(Debug)]
+    struct CountingSphere {
+        inner: Sphere,
+        calls: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl Shape for CountingSphere {
+        fn transform(&self) -> &Matrix4<f64> {
+            self.inner.transform()
+        }
+        fn material(&self) -> &Material {
+            self.inner.material()
+        }
+        fn parent_transform(&self) -> &Matrix4<f64> {
+            self.inner.parent_transform()
+        }
+        fn set_parent_transform(&mut self, m: Matrix4<f64>) {
+            self.inner.set_parent_transform(m);
+        }
+        fn local_intersect(&self, r: &Ray) -> Vec<Intersection<'_>> {
+            self.calls.set(self.calls.get() + 1);
+            let ts: Vec<f64> = self.inner.local_intersect(r).iter().map(|i| i.t).collect();
+            ts.into_iter().map(|t| intersection(t, self)).collect()
+        }
+        fn local_normal_at(&self, p: Tuple, hit: &Intersection) -> Tuple {
+            self.inner.local_normal_at(p, hit)
+        }
+        fn bounds(&self) -> BoundingBox {
+            self.inner.bounds()
+        }
+    }
+
+    fn row_of_counting_spheres(calls: &std::rc::Rc<std::cell::Cell<usize>>) -> Group {
+        let mut g = group();
+        for i in 0..32 {
+            g.add_child(Box::new(CountingSphere {
+                inner: sphere_at(i as f64 * 3.0, 0.0, 0.0),
+                calls: calls.clone(),
+            }));
+        }
+        g
     }
 
     #[test]
-    fn test_shearing_z_in_proportion_to_y() {
-        let transform = shearing(
-            0.0, 0.0, 0.0,
-            0.0, 0.0, 1.0
-        );
-        let p = Point3::new(2.0, 3.0, 4.0);
-        assert_relative_eq!(
-            transform.transform_point(&p),
-            Point3::new(2.0, 3.0, 7.0)
-        )
-    }}
+    fn test_adding_a_large_group_to_a_world_divides_it() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+
+        // pushed directly, every sphere in the group is tested
+        let mut w = world();
+        w.objects.push(Box::new(row_of_counting_spheres(&calls)));
+        assert_eq!(intersect_world(&w, &r).len(), 2);
+        assert_eq!(calls.get(), 32);
+
+        // added, the group is split and only spheres near the ray are tested
+        calls.set(0);
+        let mut w = world();
+        w.add_object(Box::new(row_of_counting_spheres(&calls)));
+        assert_eq!(intersect_world(&w, &r).len(), 2);
+        assert!(calls.get() <= BVH_THRESHOLD);
+    }
+
+    #[test]
+    fn test_dividing_a_world_divides_its_groups() {
+        let r = ray(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut w = world();
+        w.objects.push(Box::new(row_of_counting_spheres(&calls)));
+        w.divide(4);
+        assert_eq!(intersect_world(&w, &r).len(), 2);
+        assert!(calls.get() <= 4);
+    }
+}